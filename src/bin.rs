@@ -10,6 +10,7 @@ fn main() -> Result<(), RifError> {
     #[cfg(feature = "clap")]
     if let Err(error_content) =  Cli::parse() {
         println!("{}", error_content);
+        std::process::exit(1);
     }
 
     Ok(())
@@ -1,7 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use std::path::{PathBuf, Path};
 
-use crate::utils;
 use crate::rif::rel::Relations;
 use crate::models::FileStatus;
 use crate::RifError;
@@ -119,8 +118,9 @@ impl Checker {
     ///
     /// This method check files' relation with references and set file' status according to referencing files' statues.
     /// If referencing file is newer than a parent file or is stale, the parent becomes stale.
+    /// Frozen(neutral) files are neither updated nor propagate their changes to parents.
     /// # Return value
-    /// This return vector of tuples (FileStatus, FilePath) which is used by hook trigger
+    /// This return vector of tuples (FileStatus, FilePath) of changed files which is used to log status changes and by hook trigger
     ///
     /// # Args
     /// * `rif_list` - Target rif list to check references
@@ -137,6 +137,11 @@ impl Checker {
             // when there are no references.
             let mut status = FileStatus::Fresh;
 
+            // Frozen file's status is never changed by checker
+            if let Some(FileStatus::Neutral) = rif_list.files.get(target_key).map(|file| file.status) {
+                continue;
+            }
+
             // Item is a node retrieved with item_key
             if let Some(target_node) = self.node_map.get(target_key) {
                 // item_ref_keys are vector of keys which parent is the 'item'
@@ -145,6 +150,11 @@ impl Checker {
                 for key in target_ref_keys.iter() {
                     // Child single_File that is the child of node 'item'
                     if let Some(child_file) = rif_list.files.get(key) {
+                        // Frozen child doesn't affect parent's status
+                        if let FileStatus::Neutral = child_file.status {
+                            continue;
+                        }
+
                         // Made status public for debugging
                         // If child is stale, then parent is automatically stale
                         if let FileStatus::Stale = child_file.status {
@@ -152,8 +162,8 @@ impl Checker {
                             break;
                         }
 
-                        // If child is fresh but fresher than parent, then parent is stale
-                        if child_file.timestamp > rif_list.files.get(target_key).unwrap().timestamp {
+                        // If child is fresh but fresher than parent's last sync, then parent is stale
+                        if child_file.timestamp > rif_list.files.get(target_key).unwrap().synced {
                             status = FileStatus::Stale;
                             break;
                         }
@@ -166,9 +176,7 @@ impl Checker {
 
             // Set new status into rif_list
             if let Some(file) = rif_list.files.get_mut(target_key) {
-                // Print status changes into stdout
                 if file.status != status {
-                    println!("Status update \"{}\" {}", utils::green(&target_key.display().to_string()), status);
                     // Add file to changed files
                     changed_files.push((status, target_key.to_path_buf()));
                 }
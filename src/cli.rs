@@ -2,7 +2,7 @@ use std::path::Path;
 use clap::clap_app;
 use crate::RifError;
 use crate::Rif;
-use crate::models::ListType;
+use crate::models::{CyclePolicy, GraphFormat, ImportFormat, ListType};
 use crate::utils;
 
 /// Struct to parse command line arguments and execute proper operations
@@ -24,17 +24,32 @@ impl Cli {
         Cli::subcommand_add(args)?;
         Cli::subcommand_revert(args)?;
         Cli::subcommand_commit(args)?;
+        Cli::subcommand_ack(args)?;
+        Cli::subcommand_freeze(args)?;
+        Cli::subcommand_thaw(args)?;
         Cli::subcommand_remove(args)?;
         Cli::subcommand_rename(args)?;
         Cli::subcommand_set(args)?;
         Cli::subcommand_unset(args)?;
         Cli::subcommand_discard(args)?;
         Cli::subcommand_list(args)?;
+        Cli::subcommand_graph(args)?;
+        Cli::subcommand_query(args)?;
+        Cli::subcommand_report(args)?;
         Cli::subcommand_check(args)?;
         Cli::subcommand_sanity(args)?;
+        Cli::subcommand_gc(args)?;
+        Cli::subcommand_import(args)?;
+        Cli::subcommand_export(args)?;
+        Cli::subcommand_import_bundle(args)?;
+        Cli::subcommand_rebase_times(args)?;
         Cli::subcommand_status(args)?;
+        Cli::subcommand_diff(args)?;
         Cli::subcommand_depend(args)?;
         Cli::subcommand_data(args)?;
+        Cli::subcommand_watch(args)?;
+        Cli::subcommand_lsp(args)?;
+        Cli::subcommand_tui(args)?;
         Ok(())
     }
 
@@ -49,12 +64,14 @@ impl Cli {
             (@setting ArgRequiredElseHelp)
             (@subcommand add =>
                 (about: "Add file to rif")
-                (@arg FILE: ... +required "File to add")
+                (@arg FILE: ... "File to add")
                 (@arg force: -f --force "Force add")
+                (@arg query: -q --query +takes_value "Add files selected by query")
             )
             (@subcommand revert =>
                 (about: "Revert addition")
-                (@arg FILE: ... "File to revert")
+                (@arg FILE: ... "File or directory to revert")
+                (@arg query: -q --query +takes_value conflicts_with[FILE] "Revert files selected by query")
             )
             (@subcommand data =>
                 (about: "Print data as json format")
@@ -70,9 +87,11 @@ impl Cli {
                 (@arg FILE: ... +required "File to remove")
             )
             (@subcommand mv =>
-                (about: "Rename a file")
-                (@arg FILE: +required "File to rename")
-                (@arg NEWNAME: +required "New file name")
+                (about: "Rename a file or move a directory")
+                (@arg FILE: required_unless_present[detected] "File or directory to rename")
+                (@arg NEWNAME: required_unless_present[detected] "New file or directory name")
+                (@arg cached: --cached "Only update rif for files already moved by other tools")
+                (@arg detected: -d --detected conflicts_with[FILE] "Apply renames detected by status")
             )
             (@subcommand set =>
                 (about: "Set references to file")
@@ -86,7 +105,25 @@ impl Cli {
             )
             (@subcommand commit =>
                 (about: "Commit addition of files")
+                (@arg FILE: ... "Staged file or directory to commit, other staged files are left staged")
                 (@arg message: -m --message +takes_value "Message to add in update")
+                (@arg query: -q --query +takes_value conflicts_with[FILE] "Commit staged files selected by query, other staged files are left staged")
+                (@arg ("allow-deleted"): --("allow-deleted") "Commit even if deleted files are not committed")
+                (@arg edit: -e --edit "Write a message for each file with editor")
+            )
+            (@subcommand ack =>
+                (about: "Acknowledge stale files as reviewed without updating them")
+                (@arg FILE: ... "File to acknowledge")
+                (@arg message: -m --message +takes_value "Message to add in history")
+                (@arg query: -q --query +takes_value "Acknowledge files selected by query")
+            )
+            (@subcommand freeze =>
+                (about: "Freeze files so that they are excluded from staleness check")
+                (@arg FILE: ... +required "File to freeze")
+            )
+            (@subcommand thaw =>
+                (about: "Thaw frozen files")
+                (@arg FILE: ... +required "File to thaw")
             )
             (@subcommand discard =>
                 (about: "Discard file changes")
@@ -99,6 +136,33 @@ impl Cli {
             (@subcommand sanity =>
                 (about: "Check sanity of rif file")
                 (@arg fix: --fix "Fix rif sanity")
+                (@arg dry_run: --("dry-run") requires[fix] "Only print planned repairs")
+                (@arg policy: -p --policy +takes_value requires[fix] "Policy to choose an edge that breaks a loop, default is last (last|interactive)")
+            )
+            (@subcommand import =>
+                (about: "Import references from json, csv, dot or markdown links")
+                (@arg SOURCE: +required "File to import, or a directory to scan for markdown links")
+                (@arg format: -f --format +takes_value "Format of source, inferred from source if not given (json|csv|dot|markdown)")
+                (@arg merge: -m --merge "Import into a project which already tracks files")
+                (@arg dry_run: --("dry-run") "Only print what would be imported")
+            )
+            (@subcommand export =>
+                (about: "Export relations, history and config as a json bundle")
+                (@arg OUTPUT: "File to write, bundle is printed if not given")
+            )
+            (@subcommand ("import-bundle") =>
+                (about: "Import a json bundle, rif directory is created if it doesn't exist")
+                (@arg BUNDLE: +required "Bundle file to import")
+                (@arg force: -f --force "Replace files that project already tracks")
+                (@arg hooks: --hooks "Also import hooks of the bundle")
+            )
+            (@subcommand ("rebase-times") =>
+                (about: "Reconcile stored modified times with files using content hashes, e.g. after git clone")
+                (@arg dry_run: --("dry-run") "Only print files that would be rebased")
+            )
+            (@subcommand gc =>
+                (about: "Remove objects that are not referenced by snapshots or tracked files")
+                (@arg dry_run: --("dry-run") "Only print objects to remove")
             )
             (@subcommand init =>
                 (about: "Initiate working directory")
@@ -108,12 +172,46 @@ impl Cli {
                 (about: "Show current status of rif")
                 (@arg ignore: -i --ignore "Ignore untracked files")
                 (@arg verbose: -v --verbose "Also print out list")
+                (@arg staged: -s --staged "Only print staged files with reasons")
+            )
+            (@subcommand diff =>
+                (about: "Show changes of files")
+                (@arg FILE: conflicts_with[staged] "Show changes of references since the file was last synced")
+                (@arg staged: -s --staged "Show timestamps and stale files that commit of staged files makes")
+            )
+            (@subcommand watch =>
+                (about: "Watch file changes and report impact continuously")
+                (@arg add: -a --add "Stage modified and deleted files automatically")
+            )
+            (@subcommand graph =>
+                (about: "Export reference graph")
+                (@arg FILE: "Root file of subgraph to export")
+                (@arg format: -f --format +takes_value "Graph format, default is dot (dot|mermaid|json)")
+                (@arg depth: -d --depth +takes_value "Maximum reference hops from root file(unsigned integer). 0 means no limit")
+                (@arg cluster: -c --cluster "Cluster files by directory")
+                (@arg query: -q --query +takes_value "Only export files selected by query")
+            )
+            (@subcommand report =>
+                (about: "Generate static html report")
+                (@arg html: --html +takes_value +required "Directory to write html report")
+            )
+            (@subcommand query =>
+                (about: "Print files selected by query")
+                (@arg QUERY: +required "Query to select files, e.g. stale() & under(\"docs\")")
+            )
+            (@subcommand lsp =>
+                (about: "Run a language server that serves rif status to editors")
+            )
+            (@subcommand tui =>
+                (about: "Browse and edit relations in an interactive terminal ui")
             )
             (@subcommand ls =>
                 (about: "Diplay all files from rif file")
                 (@arg FILE: "File to list")
                 (@arg depth: -d --depth +takes_value "Maximum depth for display tree(unsigned integer). 0 means print a whole tree")
-                (@arg type: -t --type +takes_value "List Type, default is all (all|stale)")
+                (@arg type: -t --type +takes_value "List Type, default is all (all|stale|updated|modified|orphan|leaf|root)")
+                (@arg since: -s --since +takes_value "List files updated since given time(YYYY-MM-DD [HH:MM:SS]). Used with updated type")
+                (@arg query: -q --query +takes_value conflicts_with[type] "List files selected by query")
             )
         ).get_matches()
     }
@@ -133,7 +231,14 @@ impl Cli {
     /// Check if `add` subcommand was given and parse subcommand options
     fn subcommand_add(matches: &clap::ArgMatches) -> Result<(), RifError>{
         if let Some(sub_match) = matches.subcommand_matches("add") {
-            if let Some(files) = sub_match.values_of("FILE") {
+            if let Some(query) = sub_match.value_of("query") {
+                let force = sub_match.is_present("force");
+
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                let files = rif.select(query)?;
+                rif.add(&files, force)?;
+            } else if let Some(files) = sub_match.values_of("FILE") {
                 let files = files.into_iter().map(|s| Path::new(s)).collect();
                 let force = sub_match.is_present("force");
 
@@ -150,13 +255,18 @@ impl Cli {
     /// Check if `revert` subcommand was given and parse subcommand options
     fn subcommand_revert(matches: &clap::ArgMatches) -> Result<(), RifError>{
         if let Some(sub_match) = matches.subcommand_matches("revert") {
-            let files = sub_match
-                .values_of("FILE")
-                .map(|s| s.into_iter().map(|s| Path::new(s)).collect());
-
             let rif_path = utils::get_rif_directory()?;
             let mut rif = Rif::new(Some(&rif_path))?;
-            rif.revert(files.as_ref())?;
+
+            if let Some(query) = sub_match.value_of("query") {
+                let files = rif.select(query)?;
+                rif.revert(Some(&files))?;
+            } else {
+                let files = sub_match
+                    .values_of("FILE")
+                    .map(|s| s.into_iter().map(|s| Path::new(s)).collect());
+                rif.revert(files.as_ref())?;
+            }
         } 
         Ok(())
     }
@@ -165,14 +275,80 @@ impl Cli {
     fn subcommand_commit(matches: &clap::ArgMatches) -> Result<(), RifError>{
         if let Some(sub_match) = matches.subcommand_matches("commit") {
             let message = sub_match.value_of("message");
+            let allow_deleted = sub_match.is_present("allow-deleted");
+            let edit = sub_match.is_present("edit");
 
             let rif_path = utils::get_rif_directory()?;
             let mut rif = Rif::new(Some(&rif_path))?;
-            rif.commit(message)?;
+            if let Some(query) = sub_match.value_of("query") {
+                let files = rif.select_staged_query(query)?;
+                rif.commit(Some(&files), message, allow_deleted, edit)?;
+            } else {
+                let files: Option<Vec<&Path>> = sub_match
+                    .values_of("FILE")
+                    .map(|s| s.into_iter().map(Path::new).collect());
+                rif.commit(files.as_ref(), message, allow_deleted, edit)?;
+            }
         } 
         Ok(())
     }
 
+    /// Check if `ack` subcommand was given and parse subcommand options
+    fn subcommand_ack(matches: &clap::ArgMatches) -> Result<(), RifError>{
+        if let Some(sub_match) = matches.subcommand_matches("ack") {
+            if let Some(query) = sub_match.value_of("query") {
+                let message = sub_match.value_of("message");
+
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                let files = rif.select(query)?;
+                rif.ack(&files, message)?;
+            } else if let Some(files) = sub_match.values_of("FILE") {
+                let files: Vec<&Path> = files.into_iter().map(Path::new).collect();
+                let message = sub_match.value_of("message");
+
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.ack(&files, message)?;
+            } else {
+                eprintln!("No argument for ack");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if `freeze` subcommand was given and parse subcommand options
+    fn subcommand_freeze(matches: &clap::ArgMatches) -> Result<(), RifError>{
+        if let Some(sub_match) = matches.subcommand_matches("freeze") {
+            if let Some(files) = sub_match.values_of("FILE") {
+                let files = files.into_iter().map(Path::new).collect();
+
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.freeze(&files)?;
+            } else {
+                eprintln!("No argument for freeze");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if `thaw` subcommand was given and parse subcommand options
+    fn subcommand_thaw(matches: &clap::ArgMatches) -> Result<(), RifError>{
+        if let Some(sub_match) = matches.subcommand_matches("thaw") {
+            if let Some(files) = sub_match.values_of("FILE") {
+                let files = files.into_iter().map(Path::new).collect();
+
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.thaw(&files)?;
+            } else {
+                eprintln!("No argument for thaw");
+            }
+        }
+        Ok(())
+    }
+
     /// Check if `remove` subcommand was given and parse subcommand options
     fn subcommand_remove(matches: &clap::ArgMatches) -> Result<(), RifError>{
         if let Some(sub_match) = matches.subcommand_matches("rm") {
@@ -192,11 +368,15 @@ impl Cli {
     /// Check if `rename` subcommand was given and parse subcommand options
     fn subcommand_rename(matches: &clap::ArgMatches) -> Result<(), RifError>{
         if let Some(sub_match) = matches.subcommand_matches("mv") {
-            if let Some(source_name) = sub_match.value_of("FILE") {
+            if sub_match.is_present("detected") {
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.rename_detected()?;
+            } else if let Some(source_name) = sub_match.value_of("FILE") {
                 if let Some(new_name) = sub_match.value_of("NEWNAME") {
                     let rif_path = utils::get_rif_directory()?;
                     let mut rif = Rif::new(Some(&rif_path))?;
-                    rif.rename(source_name, new_name)?;
+                    rif.rename(source_name, new_name, sub_match.is_present("cached"))?;
                 } else {
                     eprintln!("Mv needs second argument as a new file name");
                 }
@@ -270,7 +450,17 @@ impl Cli {
     fn subcommand_list(matches: &clap::ArgMatches) -> Result<(), RifError>{
         if let Some(sub_match) = matches.subcommand_matches("ls") {
             let file = sub_match.value_of("FILE");
-            let list_type = ListType::from(sub_match.value_of("type").unwrap_or("all"));
+            let mut list_type = match sub_match.value_of("query") {
+                Some(query) => ListType::Query(query.to_owned()),
+                None => ListType::from(sub_match.value_of("type").unwrap_or("all"))?,
+            };
+            if let Some(since) = sub_match.value_of("since") {
+                if let ListType::Updated(_) = list_type {
+                    list_type = ListType::Updated(Some(utils::parse_time(since)?));
+                } else {
+                    return Err(RifError::CliError(String::from("Since option can only be used with updated list type")));
+                }
+            }
             let depth = sub_match
                 .value_of("depth")
                 .map(|num| {
@@ -284,6 +474,54 @@ impl Cli {
         Ok(())
     }
 
+    /// Check if `graph` subcommand was given and parse subcommand options
+    fn subcommand_graph(matches: &clap::ArgMatches) -> Result<(), RifError>{
+        if let Some(sub_match) = matches.subcommand_matches("graph") {
+            let file = sub_match.value_of("FILE");
+            let format = GraphFormat::from(sub_match.value_of("format").unwrap_or("dot"))?;
+            let cluster = sub_match.is_present("cluster");
+            let query = sub_match.value_of("query");
+            let depth = sub_match
+                .value_of("depth")
+                .map(|num| {
+                    num.parse::<usize>().expect("Depth value should be an unsigned integer")
+                });
+
+            let rif_path = utils::get_rif_directory()?;
+            let rif = Rif::new(Some(&rif_path))?;
+            rif.graph(file, format, depth, cluster, query)?;
+        }
+        Ok(())
+    }
+
+    /// Check if `query` subcommand was given and parse subcommand options
+    fn subcommand_query(matches: &clap::ArgMatches) -> Result<(), RifError>{
+        if let Some(sub_match) = matches.subcommand_matches("query") {
+            if let Some(query) = sub_match.value_of("QUERY") {
+                let rif_path = utils::get_rif_directory()?;
+                let rif = Rif::new(Some(&rif_path))?;
+                rif.query(query)?;
+            } else {
+                eprintln!("No query was given");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if `report` subcommand was given and parse subcommand options
+    fn subcommand_report(matches: &clap::ArgMatches) -> Result<(), RifError>{
+        if let Some(sub_match) = matches.subcommand_matches("report") {
+            if let Some(out_dir) = sub_match.value_of("html") {
+                let rif_path = utils::get_rif_directory()?;
+                let rif = Rif::new(Some(&rif_path))?;
+                rif.report(Path::new(out_dir))?;
+            } else {
+                eprintln!("No directory for report");
+            }
+        }
+        Ok(())
+    }
+
     /// Check if `check` subcommand was given and parse subcommand options
     fn subcommand_check(matches: &clap::ArgMatches) -> Result<(), RifError> {
         if let Some(_) = matches.subcommand_matches("check") {
@@ -298,23 +536,120 @@ impl Cli {
     fn subcommand_sanity(matches: &clap::ArgMatches) -> Result<(), RifError> {
         if let Some(sub_match) = matches.subcommand_matches("sanity") {
             let fix = sub_match.is_present("fix");
+            let dry_run = sub_match.is_present("dry_run");
+            let policy = CyclePolicy::from(sub_match.value_of("policy").unwrap_or("last"))?;
 
             let rif_path = utils::get_rif_directory()?;
             let mut rif = Rif::new(Some(&rif_path))?;
-            rif.sanity(fix)?;
+            rif.sanity(fix, dry_run, policy)?;
         } 
         Ok(())
     }
 
+    /// Check if `gc` subcommand was given and parse subcommand options
+    fn subcommand_gc(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("gc") {
+            let dry_run = sub_match.is_present("dry_run");
+
+            let rif_path = utils::get_rif_directory()?;
+            let mut rif = Rif::new(Some(&rif_path))?;
+            rif.gc(dry_run)?;
+        } 
+        Ok(())
+    }
+
+    /// Check if `import` subcommand was given and parse subcommand options
+    fn subcommand_import(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("import") {
+            let source = Path::new(sub_match.value_of("SOURCE").unwrap());
+            let format = match sub_match.value_of("format") {
+                Some(format) => ImportFormat::from(format)?,
+                None => ImportFormat::from_path(source)?,
+            };
+            let merge = sub_match.is_present("merge");
+            let dry_run = sub_match.is_present("dry_run");
+
+            let rif_path = utils::get_rif_directory()?;
+            let mut rif = Rif::new(Some(&rif_path))?;
+            rif.import(source, format, merge, dry_run)?;
+        } 
+        Ok(())
+    }
+
+    /// Check if `export` subcommand was given and parse subcommand options
+    fn subcommand_export(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("export") {
+            let output = sub_match.value_of("OUTPUT").map(Path::new);
+
+            let rif_path = utils::get_rif_directory()?;
+            let rif = Rif::new(Some(&rif_path))?;
+            rif.export(output)?;
+        } 
+        Ok(())
+    }
+
+    /// Check if `import-bundle` subcommand was given and parse subcommand options
+    fn subcommand_import_bundle(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("import-bundle") {
+            let bundle = Path::new(sub_match.value_of("BUNDLE").unwrap());
+            let force = sub_match.is_present("force");
+            let hooks = sub_match.is_present("hooks");
+
+            // Bundle can be imported into a new directory
+            let rif_path = match utils::get_rif_directory() {
+                Ok(path) => path,
+                Err(_) => {
+                    let current_dir :Option<&Path> = None;
+                    Rif::init(current_dir, false)?;
+                    std::env::current_dir()?
+                }
+            };
+            let mut rif = Rif::new(Some(&rif_path))?;
+            rif.import_bundle(bundle, force, hooks)?;
+        } 
+        Ok(())
+    }
+
+    /// Check if `rebase-times` subcommand was given and parse subcommand options
+    fn subcommand_rebase_times(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("rebase-times") {
+            let dry_run = sub_match.is_present("dry_run");
+
+            let rif_path = utils::get_rif_directory()?;
+            let mut rif = Rif::new(Some(&rif_path))?;
+            rif.rebase_times(dry_run)?;
+        }
+        Ok(())
+    }
+
     /// Check if `status` subcommand was given and parse subcommand options
     fn subcommand_status(matches: &clap::ArgMatches) -> Result<(), RifError> {
         if let Some(sub_match) = matches.subcommand_matches("status") {
             let ignore = sub_match.is_present("ignore");
             let verbose = sub_match.is_present("verbose");
+            let staged = sub_match.is_present("staged");
 
             let rif_path = utils::get_rif_directory()?;
             let mut rif = Rif::new(Some(&rif_path))?;
-            rif.status(ignore, verbose)?;
+            rif.status(ignore, verbose, staged)?;
+        } 
+        Ok(())
+    }
+
+    /// Check if `diff` subcommand was given and parse subcommand options
+    fn subcommand_diff(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("diff") {
+            if sub_match.is_present("staged") {
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.diff_staged()?;
+            } else if let Some(file) = sub_match.value_of("FILE") {
+                let rif_path = utils::get_rif_directory()?;
+                let rif = Rif::new(Some(&rif_path))?;
+                rif.diff(Path::new(file))?;
+            } else {
+                eprintln!("No argument for diff");
+            }
         } 
         Ok(())
     }
@@ -347,4 +682,54 @@ impl Cli {
 
         Ok(())
     }
+
+    /// Check if `watch` subcommand is given
+    fn subcommand_watch(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if let Some(sub_match) = matches.subcommand_matches("watch") {
+            #[cfg(feature = "watch")]
+            {
+                let auto_add = sub_match.is_present("add");
+
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.watch(auto_add)?;
+            }
+            #[cfg(not(feature = "watch"))]
+            {
+                let _ = sub_match;
+                return Err(RifError::CliError(String::from("Rif was built without watch feature")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if `lsp` subcommand is given
+    fn subcommand_lsp(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if matches.subcommand_matches("lsp").is_some() {
+            let rif_path = utils::get_rif_directory()?;
+            let mut rif = Rif::new(Some(&rif_path))?;
+            rif.lsp()?;
+        }
+
+        Ok(())
+    }
+
+    /// Check if `tui` subcommand is given
+    fn subcommand_tui(matches: &clap::ArgMatches) -> Result<(), RifError> {
+        if matches.subcommand_matches("tui").is_some() {
+            #[cfg(feature = "tui")]
+            {
+                let rif_path = utils::get_rif_directory()?;
+                let mut rif = Rif::new(Some(&rif_path))?;
+                rif.tui()?;
+            }
+            #[cfg(not(feature = "tui"))]
+            {
+                return Err(RifError::CliError(String::from("Rif was built without tui feature")));
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -4,11 +4,44 @@ pub const BLACK_LIST: [&str; 2] = [RIF_IGNORE_FILE, RIF_DIECTORY];
 pub const RIF_IGNORE_FILE: &str = ".rifignore";
 /// File path of rif list 
 pub const RIF_REL_FILE: &str = "rel";
+/// Backup of rif list made before sanity fix
+pub const RIF_REL_BACKUP: &str = "rel.bak";
 /// Main rif directory
 pub const RIF_DIECTORY: &str = ".rif";
 /// Update messages
 pub const RIF_HIST_FILE: &str = "history";
+/// Backup of history made before sanity fix
+pub const RIF_HIST_BACKUP: &str = "history.bak";
 /// Config
 pub const RIF_CONFIG: &str = "config";
 /// Meta
 pub const RIF_META: &str = "meta";
+/// Commit message edited by editor
+pub const RIF_COMMIT_MESSAGE: &str = "COMMIT_MESSAGE";
+/// Snapshot list of committed file contents
+pub const RIF_SNAPSHOTS: &str = "snapshots";
+/// Directory of compressed file contents
+pub const RIF_OBJECTS: &str = "objects";
+/// Reference counts of objects
+pub const RIF_OBJECT_REFS: &str = "refs";
+/// Magic bytes of a header that precedes versioned binary files
+pub const FORMAT_MAGIC: &[u8; 4] = b"RIF\0";
+/// Format version of rel file
+///
+/// Version 0 is written without a header, 1 adds synced time and 2 adds content hash of files
+pub const REL_FORMAT_VERSION: u32 = 2;
+/// Format version of meta file
+///
+/// Version 0 is written without a header and 1 adds last committed files
+pub const META_FORMAT_VERSION: u32 = 1;
+/// Format version of exported bundle
+pub const BUNDLE_VERSION: u32 = 1;
+/// Minimum content similarity ratio of a file renamed and modified outside of rif
+pub const RENAME_SIMILARITY: f32 = 0.5;
+/// Maximum size of a file whose content similarity is compared to detect a rename
+pub const RENAME_SIMILARITY_MAX_BYTES: u64 = 1024 * 1024;
+/// Zstd compression level of objects, 0 means zstd's default level
+pub const OBJECT_COMPRESSION_LEVEL: i32 = 0;
+/// Milliseconds to wait until watched changes are settled
+#[cfg(feature = "watch")]
+pub const WATCH_DEBOUNCE_MILLIS: u64 = 500;
@@ -8,12 +8,15 @@ pub enum RifError {
     ConfigError(String),
     Ext(String),
     GetFail(String),
+    HookError(String),
     InvalidFormat(String),
     IoError(std::io::Error),
+    QueryError(String),
     RenameFail(String),
     RifIoError(String),
     SerdeError(serde_json::Error),
     UpdateError(String),
+    WatchError(String),
 }
 
 impl std::fmt::Display for RifError {
@@ -28,11 +31,14 @@ impl std::fmt::Display for RifError {
             RifError::CliError(content) => write!(f, "{}", content),
             RifError::Ext(content) => write!(f, "{}", content),
             RifError::GetFail(content) => write!(f, "{}", content),
+            RifError::HookError(content) => write!(f, "{}", content),
             RifError::InvalidFormat(content) => write!(f, "{}", content),
             RifError::IoError(content) => write!(f, "{}", content),
+            RifError::QueryError(content) => write!(f, "{}", content),
             RifError::RifIoError(content) => write!(f, "{}", content),
             RifError::SerdeError(content) => write!(f, "{}", content),
             RifError::ConfigError(content) => write!(f, "{}", content),
+            RifError::WatchError(content) => write!(f, "{}", content),
         }
     }
 }
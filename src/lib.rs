@@ -11,4 +11,4 @@ mod error;
 
 pub use crate::error::RifError;
 pub use crate::rif::*;
-pub use models::ListType;
+pub use models::{CyclePolicy, GraphFormat, ImportFormat, ListType};
@@ -1,42 +1,124 @@
 use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use itertools::Itertools;
+use chrono::NaiveDateTime;
 use crate::utils;
+use crate::RifError;
 
 /// File status of any file.
 ///
 /// Fresh means a file is up to date
 /// while stale means referencing file has been updated and the file has not.
+/// Neutral means a file is frozen and excluded from staleness propagation.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub(crate) enum FileStatus {
     Fresh,
-    Neutral, // Reserved for future usages. Might be deleted after all.
+    Neutral,
     Stale,
 }
 
 impl std::fmt::Display for FileStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let FileStatus::Stale = self {
-            write!(f, "{}", utils::red("(s)"))
-        } else { // Print nothing
-            write!(f, "")
+        match self {
+            FileStatus::Stale => write!(f, "{}", utils::red("(s)")),
+            FileStatus::Neutral => write!(f, "{}", utils::blue("(f)")),
+            // Print nothing
+            FileStatus::Fresh => write!(f, ""),
         }
     }
 }
 
-/// Sanity type to branch sanity check.
-///
-/// Direct only check self referencing while indirect also checks infinite loop
-pub(crate) enum SanityType {
-    Direct,
-    Indirect
+/// Kind of change staged to be committed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum StagedChange {
+    New,
+    Modified,
+    Forced,
+    Deleted,
 }
 
-/// Medium variable to check reference status.
-///
-/// This enumerator is used for sanity checking and determines whether rif
-/// sanity is assured or not.
-pub(crate) enum RefStatus {
-    Invalid,
-    Valid
+impl std::fmt::Display for StagedChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Labels are padded to be aligned in status
+        match self {
+            StagedChange::New => write!(f, "new file"),
+            StagedChange::Modified => write!(f, "modified"),
+            StagedChange::Forced => write!(f, "forced  "),
+            StagedChange::Deleted => write!(f, "deleted "),
+        }
+    }
+}
+
+/// Single repair planned by sanity fix
+#[derive(Debug)]
+pub(crate) enum Repair {
+    /// Drop a reference to an untracked file
+    DropReference { file: PathBuf, reference: PathBuf },
+    /// Drop a reference to break a loop
+    BreakCycle { file: PathBuf, reference: PathBuf, cycle: Vec<PathBuf> },
+    /// Queue a missing file as deleted
+    MarkDeleted(PathBuf),
+    /// Move history of an untracked file to a tracked file
+    MoveHistory { from: PathBuf, to: PathBuf },
+    /// Drop history of an untracked file
+    DropHistory(PathBuf),
+}
+
+impl std::fmt::Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repair::DropReference { file, reference } => {
+                write!(f, "drop dangling reference : {} -> {}", file.display(), reference.display())
+            }
+            Repair::BreakCycle { file, reference, cycle } => {
+                let cycle = cycle.iter().map(|path| path.display().to_string()).join(" -> ");
+                write!(f, "break loop at edge : {} -> {} (loop : {})", file.display(), reference.display(), cycle)
+            }
+            Repair::MarkDeleted(file) => write!(f, "mark missing file as deleted : {}", file.display()),
+            Repair::MoveHistory { from, to } => write!(f, "move orphan history : {} -> {}", from.display(), to.display()),
+            Repair::DropHistory(file) => write!(f, "drop orphan history : {}", file.display()),
+        }
+    }
+}
+
+/// Reason why a deleted file is matched with a new file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum RenameMatch {
+    /// Content hash is identical
+    Content,
+    /// Content is similar enough, with a similarity ratio between 0 and 1
+    Similar(f32),
+}
+
+/// Rename detected from a deleted tracked file to an untracked file
+#[derive(Debug)]
+pub(crate) struct DetectedRename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub matched: RenameMatch,
+}
+
+impl std::fmt::Display for DetectedRename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.matched {
+            RenameMatch::Content => String::from("identical content"),
+            RenameMatch::Similar(ratio) => format!("{:.0}% similar, modified", ratio * 100.0),
+        };
+        write!(f, "{} -> {} ({})", self.from.display(), self.to.display(), reason)
+    }
+}
+
+/// Result of rebasing stored modified times against working tree
+#[derive(Debug, Default)]
+pub(crate) struct RebaseReport {
+    /// Files whose contents are same with stored hashes
+    pub rebased: Vec<PathBuf>,
+    /// Files whose contents differ from stored hashes
+    pub modified: Vec<PathBuf>,
+    /// Files that don't exist
+    pub missing: Vec<PathBuf>,
+    /// Files without stored hashes
+    pub unknown: Vec<PathBuf>,
 }
 
 /// Loop diversion enumerator
@@ -52,19 +134,106 @@ pub(crate) enum LoopBranch {
 pub enum ListType {
     All,
     Stale,
-    None,
+    /// Files updated since given time or by the last commit if time is none
+    Updated(Option<NaiveDateTime>),
+    /// Files modified but not committed yet
+    Modified,
+    /// Files without references and dependents
+    Orphan,
+    /// Files without references
+    Leaf,
+    /// Files without dependents
+    Root,
+    /// Files selected by a query
+    Query(String),
 }
 
 impl ListType {
-    pub fn from(raw : &str) -> Self {
+    pub fn from(raw : &str) -> Result<Self, RifError> {
         match raw.to_lowercase().as_str() {
-            "all" => {
-                Self::All
-            }
-            "stale" => {
-                Self::Stale
-            }
-            _ => Self::None,
+            "all" => Ok(Self::All),
+            "stale" => Ok(Self::Stale),
+            "updated" => Ok(Self::Updated(None)),
+            "modified" => Ok(Self::Modified),
+            "orphan" => Ok(Self::Orphan),
+            "leaf" => Ok(Self::Leaf),
+            "root" => Ok(Self::Root),
+            _ => Err(RifError::CliError(format!("Unknown list type : {}. Available types are (all|stale|updated|modified|orphan|leaf|root)", raw))),
+        }
+    }
+}
+
+/// Policy to choose an edge that breaks a reference loop
+#[derive(Debug, Clone, Copy)]
+pub enum CyclePolicy {
+    /// Break the edge that closes the reported loop path
+    Last,
+    /// Ask user which edge to break
+    Interactive,
+}
+
+impl CyclePolicy {
+    pub fn from(raw : &str) -> Result<Self, RifError> {
+        match raw.to_lowercase().as_str() {
+            "last" => Ok(Self::Last),
+            "interactive" => Ok(Self::Interactive),
+            _ => Err(RifError::CliError(format!("Unknown policy : {}. Available policies are (last|interactive)", raw))),
+        }
+    }
+}
+
+/// Format of a source to import references from
+#[derive(Debug, Clone, Copy)]
+pub enum ImportFormat {
+    Json,
+    Csv,
+    Dot,
+    Markdown,
+}
+
+impl ImportFormat {
+    pub fn from(raw : &str) -> Result<Self, RifError> {
+        match raw.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "dot" => Ok(Self::Dot),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(RifError::CliError(format!("Unknown import format : {}. Available formats are (json|csv|dot|markdown)", raw))),
+        }
+    }
+
+    /// Infer format from a source path
+    ///
+    /// Directory is scanned for markdown links
+    pub fn from_path(path: &Path) -> Result<Self, RifError> {
+        if path.is_dir() {
+            return Ok(Self::Markdown);
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(Self::Json),
+            Some("csv") => Ok(Self::Csv),
+            Some("dot") | Some("gv") => Ok(Self::Dot),
+            Some("md") | Some("markdown") => Ok(Self::Markdown),
+            _ => Err(RifError::CliError(format!("Failed to infer import format of \"{}\". Give format with --format option", path.display()))),
+        }
+    }
+}
+
+/// Format of exported reference graph
+#[derive(Debug, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl GraphFormat {
+    pub fn from(raw : &str) -> Result<Self, RifError> {
+        match raw.to_lowercase().as_str() {
+            "dot" => Ok(Self::Dot),
+            "mermaid" => Ok(Self::Mermaid),
+            "json" => Ok(Self::Json),
+            _ => Err(RifError::CliError(format!("Unknown graph format : {}. Available formats are (dot|mermaid|json)", raw))),
         }
     }
 }
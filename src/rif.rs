@@ -3,15 +3,34 @@ pub mod config;
 pub mod history;
 pub mod hook;
 pub mod meta;
+mod bundle;
+mod cycle;
+mod graph;
+mod import;
+mod query;
+mod report;
+mod lsp;
+mod message;
+pub(crate) mod objects;
+mod snapshot;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "tui")]
+mod tui;
 
 use crate::checker::Checker;
-use crate::models::{LoopBranch, ListType};
+use crate::models::{CyclePolicy, FileStatus, GraphFormat, ImportFormat, LoopBranch, ListType, RebaseReport, RenameMatch, Repair, StagedChange};
 use crate::utils;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use itertools::Itertools;
 use config::Config;
-use rel::Relations;
+use hook::{HookEvent, HookFile, HookPayload};
+use rel::{Relations, SingleFile};
 use history::History;
 use meta::Meta;
+use bundle::Bundle;
+use objects::ObjectStore;
+use snapshot::Snapshots;
 use crate::RifError;
 use std::path::{Path, PathBuf};
 use crate::consts::*;
@@ -22,6 +41,8 @@ pub struct Rif {
     history: History,
     relation: Relations,
     meta: Meta,
+    snapshots: Snapshots,
+    objects: ObjectStore,
     black_list: HashSet<PathBuf>,
     root_path: Option<PathBuf>,
 }
@@ -36,6 +57,8 @@ impl Rif {
             history: History::read_from_file(path.as_ref())?,
             relation: Relations::read_from_file(path.as_ref())?,
             meta: Meta::read_from_file(path.as_ref())?,
+            snapshots: Snapshots::read_from_file(path.as_ref())?,
+            objects: ObjectStore::read_from_file(path.as_ref())?,
             black_list,
             root_path: path.map(|p| p.as_ref().clone().to_owned()),
         })
@@ -86,11 +109,17 @@ impl Rif {
     /// Files that modified, newly created, deleted files can be added but non modiifed files can
     /// alos be added with force option
     pub fn add(&mut self, files: &Vec<impl AsRef<Path>>, force: bool) -> Result<(), RifError> {
+        let arguments = self.hook_arguments(files.iter().map(|file| file.as_ref()));
+        self.trigger_hooks(HookEvent::PreAdd, arguments, None)?;
+
         for file in files {
             let mut path = file.as_ref().to_owned();
 
-            // If file doesn't exist, simply ignore
+            // If file doesn't exist, queue tracked file as deleted or simply ignore
             if !path.exists() {
+                if self.relation.files.contains_key(&path) {
+                    self.meta.queue_deleted(&path);
+                }
                 continue;
             }
 
@@ -127,13 +156,37 @@ impl Rif {
 
     /// Revert added files
     ///
+    /// Any kind of staged change can be reverted and a directory reverts every staged file inside it.
     /// No files arugment revert all added files
     pub fn revert(&mut self, files: Option<&Vec<impl AsRef<Path>>>) -> Result<(), RifError> {
         if let Some(files) = files {
             for file in files {
                 let path = file.as_ref();
+
+                // "." means every staged file
+                if path.to_str().unwrap() == "." {
+                    self.meta.clear();
+                    continue;
+                }
+
                 // Removes single item
-                self.meta.remove_add_queue(&path);
+                if self.meta.remove_add_queue(&path) {
+                    continue;
+                }
+
+                // Removes every staged item inside a directory
+                let inside: Vec<PathBuf> = self.meta
+                    .get_staged()
+                    .into_iter()
+                    .map(|(staged, _)| staged)
+                    .filter(|staged| staged.starts_with(path))
+                    .collect();
+                if inside.is_empty() {
+                    eprintln!("\"{}\" is not staged", path.display());
+                }
+                for staged in inside {
+                    self.meta.remove_add_queue(&staged);
+                }
             } // for loop end
         } else {
             // No argument, revert everything
@@ -146,14 +199,51 @@ impl Rif {
 
     /// Commit addition to rif struct and check impact
     ///
-    /// Message is saved inside history file
-    pub fn commit(&mut self, message: Option<&str>) -> Result<(), RifError> {
+    /// Message is saved inside history file.
+    /// Given files or directories only commit staged files inside them and others stay staged.
+    /// Deleted files that are not committed reject the commit unless allow_deleted is set.
+    /// Edit option opens an editor to write a message for each file.
+    pub fn commit(&mut self, files: Option<&Vec<impl AsRef<Path>>>, message: Option<&str>, allow_deleted: bool, edit: bool) -> Result<(), RifError> {
+        let selected = match files {
+            Some(files) => self.select_staged(files)?,
+            None => self.meta.to_be_added_later().cloned().collect(),
+        };
 
         // Literaly, commit needs to resolve all deleted files
-        if self.relation.get_deleted_files().len() != self.meta.to_be_deleted.len() {
-            return Err(RifError::CommitFail("Commit without deleted files are illegal. Rejected".to_owned()))
+        let pending_deleted: Vec<PathBuf> = self.relation
+            .get_deleted_files()
+            .into_iter()
+            .filter(|file| !(self.meta.to_be_deleted.contains(file) && selected.contains(file)))
+            .sorted()
+            .collect();
+        if !pending_deleted.is_empty() && !allow_deleted {
+            let files = pending_deleted.iter().map(|file| format!("    {}", file.display())).join("\n");
+            return Err(RifError::CommitFail(format!("Commit without deleted files are illegal. Stage them with \"rif add\" or use --allow-deleted\n{}", files)));
         }
 
+        // Files that are not selected are queued again after commit
+        let unselected = self.meta.split_unselected(&selected);
+
+        let staged: Vec<PathBuf> = self.meta.to_be_added_later().cloned().collect();
+        let arguments = self.hook_arguments(staged.iter().map(|file| file.as_path()));
+        let prepared = self
+            .get_commit_messages(message, edit)
+            .and_then(|messages| self.trigger_hooks(HookEvent::PreCommit, arguments, messages.0.as_deref()).map(|_| messages));
+        let (message, messages) = match prepared {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                self.meta.merge(unselected);
+                return Err(err);
+            }
+        };
+        let message = message.as_deref();
+
+        // Hashes before commit to find contents to store
+        let previous: HashMap<PathBuf, Option<String>> = self.relation.files
+            .iter()
+            .map(|(path, file)| (path.clone(), file.hash.clone()))
+            .collect();
+
         // delete
         for file in self.meta.to_be_deleted.clone().iter() {
             self.remove_file(file)?;
@@ -161,12 +251,17 @@ impl Rif {
 
         // Register new files
         for file in self.meta.to_be_registerd.clone().into_iter() {
-            self.register_new_file(&file, message)?;
+            self.register_new_file(&file, messages.get(&file).map(|msg| msg.as_str()))?;
         }
 
         // force updates
         for file in self.meta.to_be_forced.iter() {
             self.relation.update_filestamp_force(&file)?;
+
+            // Add message to history
+            if let Some(msg) = messages.get(file) {
+                self.history.add_history(file, msg)?;
+            }
         }
 
         // updates
@@ -174,28 +269,141 @@ impl Rif {
             self.relation.update_filestamp(&file)?;
 
             // Add message to history
-            if let Some(msg) = message {
-                self.history.add_history(&file, msg)?;
-                self.history.save_to_file(self.root_path.as_ref())?;
+            if let Some(msg) = messages.get(file) {
+                self.history.add_history(file, msg)?;
+            }
+        }
+
+        // Keep last committed contents that relations refer to
+        for (path, file) in self.relation.files.iter() {
+            let old_hash = previous.get(path).cloned().flatten();
+            if file.hash.is_none() || file.hash == old_hash {
+                continue;
+            }
+            self.objects.put(&std::fs::read(path)?)?;
+            if let Some(old_hash) = old_hash {
+                self.objects.release(&old_hash);
+            }
+        }
+
+        // Keep contents of committed files to show diffs later
+        if self.config.snapshot {
+            let committed = self.meta.to_be_registerd.iter().chain(self.meta.to_be_forced.iter()).chain(self.meta.to_be_added.iter());
+            for file in committed {
+                if let Some(single) = self.relation.files.get(file) {
+                    self.snapshots.take(&mut self.objects, file, single.timestamp)?;
+                }
             }
         }
 
         // Check if added files are not empty
+        let mut changed_files = vec![];
         if self.meta.to_be_added_later().count() != 0 {
-            self.check_exec()?;
+            changed_files = self.check_exec()?;
         }
 
-        // Clear meta
+        // Remember committed files so that they can be listed later
+        self.meta.last_committed = staged
+            .iter()
+            .filter(|file| self.relation.files.contains_key(*file))
+            .cloned()
+            .collect();
+
+        // Clear meta but leave files that were not committed
         self.meta.clear();
+        self.meta.merge(unselected);
 
         // Save files
         self.meta.save_to_file(self.root_path.as_ref())?;
         self.relation.save_to_file(self.root_path.as_ref())?;
         self.history.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+
+        self.trigger_check_hooks(changed_files, message)?;
+        let arguments = self.hook_arguments(staged.iter().map(|file| file.as_path()));
+        self.trigger_hooks(HookEvent::PostCommit, arguments, message)?;
 
         Ok(())
     }
     
+    /// Acknowledge stale files as reviewed
+    ///
+    /// Acknowledgement clears stale status without updating file's timestamp
+    /// and message is saved inside history file.
+    /// References are acknowledged before files that reference them.
+    pub fn ack(&mut self, files: &[impl AsRef<Path>], message: Option<&str>) -> Result<(), RifError> {
+        self.ack_files(files, message, false)
+    }
+
+    /// Acknowledge stale files
+    ///
+    /// # Args
+    ///
+    /// * `files` - Files to acknowledge
+    /// * `message` - Message to save in history
+    /// * `quiet` - Don't print status changes nor trigger hooks, which is for language server
+    pub(crate) fn ack_files(&mut self, files: &[impl AsRef<Path>], message: Option<&str>, quiet: bool) -> Result<(), RifError> {
+        let files: Vec<&Path> = files.iter().map(|file| file.as_ref()).collect();
+        for path in self.relation.sort_by_references(&files) {
+            let path = path.as_path();
+            self.relation.acknowledge(path)?;
+            if let Some(msg) = message {
+                self.history.add_history(path, &format!("Acknowledged : {}", msg))?;
+            } else {
+                self.history.add_history(path, "Acknowledged")?;
+            }
+        }
+
+        // Files that reference acknowledged files can be fresh again
+        let changed_files = if quiet {
+            let mut checker = Checker::with_relations(&self.relation)?;
+            checker.check(&mut self.relation)?;
+            vec![]
+        } else {
+            self.check_exec()?
+        };
+
+        self.relation.save_to_file(self.root_path.as_ref())?;
+        self.history.save_to_file(self.root_path.as_ref())?;
+
+        if !quiet {
+            self.trigger_check_hooks(changed_files, message)?;
+        }
+        Ok(())
+    }
+
+    /// Freeze files
+    ///
+    /// Frozen files are excluded from staleness propagation until thawed
+    pub fn freeze(&mut self, files: &Vec<impl AsRef<Path>>) -> Result<(), RifError> {
+        for file in files {
+            self.relation.freeze(file.as_ref())?;
+        }
+
+        // Files that reference frozen files are not affected by them anymore
+        let changed_files = self.check_exec()?;
+
+        self.relation.save_to_file(self.root_path.as_ref())?;
+
+        self.trigger_check_hooks(changed_files, None)?;
+        Ok(())
+    }
+
+    /// Thaw frozen files
+    pub fn thaw(&mut self, files: &Vec<impl AsRef<Path>>) -> Result<(), RifError> {
+        for file in files {
+            self.relation.thaw(file.as_ref())?;
+        }
+
+        let changed_files = self.check_exec()?;
+
+        self.relation.save_to_file(self.root_path.as_ref())?;
+
+        self.trigger_check_hooks(changed_files, None)?;
+        Ok(())
+    }
+
     /// Discard file change and updated filestamp
     ///
     /// This cannot be reverted so multiple files are not supported
@@ -211,16 +419,24 @@ impl Rif {
     // Whether sany check is activated so that insane rename should be not executed
     /// Rename rif file 
     ///
-    /// If file exist, change the file name in filesystem
-    pub fn rename(&mut self, source_name: &str, new_name: &str) -> Result<(), RifError> {
+    /// If file exist, change the file name in filesystem.
+    /// If source is a directory, every tracked file under the directory is moved.
+    /// Filesystem is not changed if cached is true, which is useful when files were moved by other tools.
+    pub fn rename(&mut self, source_name: &str, new_name: &str, cached: bool) -> Result<(), RifError> {
         let source_name = Path::new(source_name);
         let new_name = Path::new(new_name);
+
+        // Directory is not a key of relations but a prefix of keys
+        if !self.relation.files.contains_key(source_name) && self.relation.files.keys().any(|path| path.starts_with(source_name)) {
+            return self.rename_directory(source_name, new_name, cached);
+        }
+
         if let Some(_) = self.relation.files.get(new_name) {
             return Err(RifError::RenameFail(format!("Rename target: \"{}\" already exists", new_name.display())));
         }
 
         // Rename file if it exsits and inside relation files
-        if source_name.exists() && self.relation.files.contains_key(source_name) {
+        if !cached && source_name.exists() && self.relation.files.contains_key(source_name) {
             if !new_name.exists() {
                 std::fs::rename(source_name, new_name)?;
             } else {
@@ -229,17 +445,110 @@ impl Rif {
         }
 
         self.relation.rename_file(source_name, new_name)?;
+        self.record_rename(source_name, new_name)?;
+        self.meta.rename_files(&[(source_name.to_owned(), new_name.to_owned())]);
+        self.relation.save_to_file(self.root_path.as_ref())?;
+        self.history.save_to_file(self.root_path.as_ref())?;
+        self.meta.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+
+        self.trigger_rename_hooks(source_name, new_name)?;
+        Ok(())
+    }
+
+    /// Move a tracked directory
+    ///
+    /// Relations keys, references, history keys and queued files under the directory
+    /// are rewritten at once after the directory is moved in filesystem.
+    fn rename_directory(&mut self, source_dir: &Path, new_dir: &Path, cached: bool) -> Result<(), RifError> {
+        let renames: Vec<(PathBuf, PathBuf)> = self.relation.files
+            .keys()
+            .filter(|path| path.starts_with(source_dir))
+            .sorted()
+            .map(|path| (path.to_owned(), new_dir.join(path.strip_prefix(source_dir).unwrap())))
+            .collect();
+
+        // Validate before touching filesystem
+        self.relation.validate_renames(&renames)?;
+        if !cached {
+            if new_dir.exists() {
+                return Err(RifError::RenameFail(format!("Rename target: \"{}\" already exists", new_dir.display())));
+            }
+            if let Some(parent) = new_dir.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(source_dir, new_dir)?;
+        }
+
+        self.relation.rename_files(&renames)?;
+        for (source, target) in renames.iter() {
+            self.record_rename(source, target)?;
+        }
+        self.meta.rename_files(&renames);
+
+        self.relation.save_to_file(self.root_path.as_ref())?;
+        self.history.save_to_file(self.root_path.as_ref())?;
+        self.meta.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+        println!("Moved {} files from \"{}\" to \"{}\"", renames.len(), source_dir.display(), new_dir.display());
+
+        for (source, target) in renames.iter() {
+            self.trigger_rename_hooks(source, target)?;
+        }
+        Ok(())
+    }
+
+    /// Apply renames detected from deleted and untracked files
+    ///
+    /// References and history follow the new name. Renamed file whose content
+    /// has also changed is left as modified.
+    pub fn rename_detected(&mut self) -> Result<(), RifError> {
+        let detected = self.relation.detect_renames(&self.black_list, &self.objects)?;
+        if detected.is_empty() {
+            println!("No renames were detected");
+            return Ok(());
+        }
+
+        for rename in detected.iter() {
+            let last_modified = self.relation.files.get(&rename.from).unwrap().last_modified;
+            self.relation.rename_file(&rename.from, &rename.to)?;
+            if let RenameMatch::Similar(_) = rename.matched {
+                self.relation.files.get_mut(&rename.to).unwrap().last_modified = last_modified;
+            }
+            self.record_rename(&rename.from, &rename.to)?;
+
+            // Rename resolves both deletion and registration
+            self.meta.to_be_deleted.remove(&rename.from);
+            self.meta.to_be_registerd.remove(&rename.to);
+            println!("Renamed {}", rename);
+        }
+
         self.relation.save_to_file(self.root_path.as_ref())?;
+        self.history.save_to_file(self.root_path.as_ref())?;
+        self.meta.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+
+        for rename in detected.iter() {
+            self.trigger_rename_hooks(&rename.from, &rename.to)?;
+        }
         Ok(())
     }
 
     /// Remove file from rif
     pub fn remove(&mut self, files: &Vec<impl AsRef<Path>>) -> Result<(), RifError> {
+        let arguments = self.hook_arguments(files.iter().map(|file| file.as_ref()));
         for file in files {
             self.remove_file(file.as_ref())?;
         }
         self.relation.save_to_file(self.root_path.as_ref())?;
         self.history.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+
+        self.trigger_hooks(HookEvent::PostRemove, arguments, None)?;
         Ok(())
     }
 
@@ -262,36 +571,34 @@ impl Rif {
     }
 
     /// Show current status of rif project
-    pub fn status(&mut self, ignore: bool, verbose: bool) -> Result<(), RifError> {
+    ///
+    /// Staged option only prints staged files with reasons why they are staged
+    pub fn status(&mut self, ignore: bool, verbose: bool, staged: bool) -> Result<(), RifError> {
         // Remove deleted files from to be added.
         self.meta.remove_non_exsitent();
 
-        let mut to_be_added_later = self.meta.to_be_added_later().peekable();
+        if staged {
+            self.print_staged(true)?;
+            self.meta.save_to_file(self.root_path.as_ref())?;
+            return Ok(());
+        }
 
-        if let Some(_) = to_be_added_later.peek() {
-            println!("# Changes to be commited :");
-            for item in &self.meta.to_be_registerd {
-                let format = format!("    new file : {}", &item.to_str().unwrap());
-                println!("{}", utils::green(&format));
-            }
-            for item in &self.meta.to_be_added {
-                let format = format!("    modified : {}", &item.to_str().unwrap());
-                println!("{}", utils::green(&format));
-            }
-            for item in &self.meta.to_be_forced {
-                let format = format!("    forced   : {}", &item.to_str().unwrap());
-                println!("{}", utils::green(&format));
-            }
-            for item in &self.meta.to_be_deleted {
-                let format = format!("    deleted  : {}", &item.to_str().unwrap());
-                println!("{}", utils::green(&format));
-            }
-            println!("");
+        if self.meta.to_be_added_later().next().is_some() {
+            self.print_staged(false)?;
+            println!();
         }
     
         println!("# Changed files :");
         self.relation.track_modified_files(self.meta.to_be_added_later())?;
 
+        let detected = self.relation.detect_renames(&self.black_list, &self.objects)?;
+        if !detected.is_empty() {
+            println!("\n# Detected renames : (use \"rif mv --detected\" to apply)");
+            for rename in detected.iter() {
+                println!("{}", utils::yellow(&format!("    renamed  : {}", rename)));
+            }
+        }
+
         // Ignore untracked files
         if !ignore {
             // Default black list only includes .rif file for now
@@ -311,6 +618,302 @@ impl Rif {
         Ok(())
     }
 
+    /// Show what staged files will change when committed
+    ///
+    /// Commit is simulated on a copy of relations, thus nothing is saved.
+    /// Changed timestamps of staged files and files that will become stale are printed.
+    pub fn diff_staged(&mut self) -> Result<(), RifError> {
+        self.meta.remove_non_exsitent();
+        let staged = self.meta.get_staged();
+        if staged.is_empty() {
+            println!("Nothing is staged");
+            return Ok(());
+        }
+
+        let simulated = self.simulate_commit()?;
+        let to_be_stale = self.get_to_be_stale(&simulated);
+
+        println!("# Staged changes :");
+        for (path, change) in staged.iter() {
+            println!("{}", utils::green(&format!("    {} : {}", change, path.display())));
+            let old = self.relation.files.get(path);
+            let new = simulated.files.get(path);
+            match (old, new) {
+                (Some(old), Some(new)) => println!("        timestamp : {} -> {}", old.timestamp, new.timestamp),
+                (None, Some(new)) => println!("        timestamp : {}", new.timestamp),
+                (Some(old), None) => println!("        removed, last timestamp was {}", old.timestamp),
+                (None, None) => {}
+            }
+
+            let dependents = self.get_stale_dependents(&simulated, &to_be_stale, path)?;
+            if !dependents.is_empty() {
+                let dependents = dependents.iter().map(|dependent| dependent.display().to_string()).join(", ");
+                println!("{}", utils::red(&format!("        makes stale : {}", dependents)));
+            }
+        }
+
+        if !to_be_stale.is_empty() {
+            println!("\n# Files to become stale :");
+            for path in to_be_stale.iter().sorted() {
+                println!("{}", utils::red(&format!("    {}", path.display())));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Show what references of a file changed since the file was last synced
+    ///
+    /// Each reference updated after the file was synced is compared with its snapshot
+    /// taken at that time, thus snapshot option should be enabled in config.
+    pub fn diff(&self, file: &Path) -> Result<(), RifError> {
+        let single = self.relation.files
+            .get(file)
+            .ok_or_else(|| RifError::GetFail(format!("\"{}\" is not tracked", file.display())))?;
+
+        let updated: Vec<&PathBuf> = single.references
+            .iter()
+            .filter(|reference| {
+                self.relation.files.get(*reference).map(|reference| reference.timestamp > single.synced).unwrap_or(false)
+            })
+            .sorted()
+            .collect();
+        if updated.is_empty() {
+            println!("No references of \"{}\" were updated since {}", file.display(), single.synced);
+            return Ok(());
+        }
+
+        for reference in updated {
+            println!("# {} : updated since {}", reference.display(), single.synced);
+            let snapshot = match self.snapshots.get_snapshot_at(reference, single.synced) {
+                Some(snapshot) => snapshot,
+                None => {
+                    println!("{}", utils::yellow("    No snapshot was taken before, enable snapshot in config to keep contents"));
+                    continue;
+                }
+            };
+            if !reference.exists() {
+                println!("{}", utils::red("    File doesn't exist"));
+                continue;
+            }
+
+            let old = self.snapshots.read_content(&self.objects, snapshot)?;
+            let new = std::fs::read(reference)?;
+            let old_name = format!("{} ({})", reference.display(), snapshot.timestamp);
+            let new_name = reference.display().to_string();
+            print!("{}", snapshot::unified_diff(&String::from_utf8_lossy(&old), &String::from_utf8_lossy(&new), &old_name, &new_name));
+        }
+        Ok(())
+    }
+
+    /// Remove objects that are not referenced by snapshots or tracked files
+    ///
+    /// Dry run only prints objects to remove
+    pub fn gc(&mut self, dry_run: bool) -> Result<(), RifError> {
+        let mut refs = self.snapshots.count_refs();
+        for (hash, count) in self.relation.count_refs() {
+            *refs.entry(hash).or_default() += count;
+        }
+        let report = self.objects.gc(refs, dry_run)?;
+        if report.removed.is_empty() {
+            println!("No unreachable objects");
+            return Ok(());
+        }
+
+        for hash in report.removed.iter() {
+            println!("    {}", hash);
+        }
+        if dry_run {
+            println!("{} objects({} bytes) would be removed", report.removed.len(), report.freed_bytes);
+        } else {
+            self.objects.save_to_file(self.root_path.as_ref())?;
+            println!("Removed {} objects({} bytes)", report.removed.len(), report.freed_bytes);
+        }
+        Ok(())
+    }
+
+    /// Import references from a source
+    ///
+    /// Files that are referenced in the source are registered if they are not tracked yet.
+    /// Edges whose files don't exist or are ignored are reported as unresolved and skipped.
+    /// Nothing is imported if result is not sane, e.g. imported references make a loop.
+    /// # Args
+    ///
+    /// * `source` - File to import, or a directory to scan for markdown links
+    /// * `format` - Format of the source
+    /// * `merge` - Whether to import into a project which already tracks files
+    /// * `dry_run` - Only print what would be imported
+    pub fn import(&mut self, source: &Path, format: ImportFormat, merge: bool, dry_run: bool) -> Result<(), RifError> {
+        if !merge && !self.relation.files.is_empty() {
+            return Err(RifError::AddFail("Project already tracks files. Use --merge to import into it".to_owned()));
+        }
+
+        let edges = import::read_edges(source, format, &self.black_list)?;
+        let mut relations = self.relation.clone();
+        let mut registered = vec![];
+        let mut unresolved: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+        let mut imported = 0;
+        // Absolute paths are relativized and paths outside of root directory are unresolved
+        let resolve = |path: &Path| utils::relativize_path(path).ok().filter(|path| {
+            path.is_relative() && !path.starts_with("..") && path.is_file()
+                && !path.ancestors().any(|ancestor| self.black_list.contains(ancestor))
+        });
+        for (from, to) in edges {
+            let (from, to) = match (resolve(&from), resolve(&to)) {
+                (Some(resolved_from), Some(resolved_to)) => (resolved_from, resolved_to),
+                (None, _) => {
+                    unresolved.entry(from.to_owned()).or_insert(from);
+                    continue;
+                }
+                (_, None) => {
+                    unresolved.entry(to).or_insert(from);
+                    continue;
+                }
+            };
+
+            for path in [&from, &to].iter().copied() {
+                if !relations.files.contains_key(path) {
+                    // Imported file is up to date as of its modified time
+                    let mut file = SingleFile::new(path.to_owned());
+                    let unix_time = utils::get_file_unix_time(path)?;
+                    file.last_modified = unix_time;
+                    file.timestamp = unix_time;
+                    file.synced = unix_time;
+                    relations.files.insert(path.to_owned(), file);
+                    registered.push(path.to_owned());
+                }
+            }
+            if relations.files.get_mut(&from).unwrap().references.insert(to) {
+                imported += 1;
+            }
+        }
+
+        if let Err(err) = relations.sanity_check() {
+            return Err(RifError::InvalidFormat(format!("Imported references are not sane. Nothing was imported\n{}", err)));
+        }
+
+        if !unresolved.is_empty() {
+            println!("# Unresolved paths :");
+            for (path, file) in unresolved.iter() {
+                println!("{}", utils::red(&format!("    {} (from {})", path.display(), file.display())));
+            }
+        }
+
+        if dry_run {
+            println!("{} files would be registered and {} references would be imported", registered.len(), imported);
+            return Ok(());
+        }
+
+        self.relation = relations;
+        for file in registered.iter() {
+            self.history.add_history(file, &format!("Imported from {}", source.display()))?;
+            // Content hash of registered file refers to stored content
+            self.objects.put(&std::fs::read(file)?)?;
+            if self.config.snapshot {
+                let timestamp = self.relation.files.get(file).unwrap().timestamp;
+                self.snapshots.take(&mut self.objects, file, timestamp)?;
+            }
+        }
+        self.relation.save_to_file(self.root_path.as_ref())?;
+        self.history.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+
+        println!("Registered {} files and imported {} references", registered.len(), imported);
+        Ok(())
+    }
+
+    /// Export project state as a json bundle
+    ///
+    /// Bundle is printed to stdout if output is not given
+    pub fn export(&self, output: Option<&Path>) -> Result<(), RifError> {
+        let bundle = Bundle::new(self.relation.clone(), self.history.clone(), self.config.clone());
+        let json = bundle.to_json()?;
+        match output {
+            Some(output) => {
+                std::fs::write(output, json)?;
+                println!("Exported {} files to \"{}\"", self.relation.files.len(), output.display());
+            }
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+
+    /// Import project state from a json bundle
+    ///
+    /// Modified times are rebased against local files using content hashes,
+    /// thus files are modified only if their contents differ from the bundle.
+    /// Staged files are cleared and snapshots of files that the bundle doesn't track are removed.
+    /// Hooks are imported only if hooks option is set because they execute commands.
+    /// # Args
+    ///
+    /// * `path` - Bundle file to import
+    /// * `force` - Replace files that project already tracks
+    /// * `hooks` - Also import hooks of the bundle
+    pub fn import_bundle(&mut self, path: &Path, force: bool, hooks: bool) -> Result<(), RifError> {
+        if !force && !self.relation.files.is_empty() {
+            return Err(RifError::AddFail("Project already tracks files. Use --force to replace them with the bundle".to_owned()));
+        }
+
+        let mut bundle = Bundle::read_from_file(path)?;
+        let report = bundle.relations.rebase_times()?;
+
+        if !hooks {
+            if !bundle.config.hooks.is_empty() {
+                println!("Hooks of the bundle were not imported. Use --hooks to import them");
+            }
+            bundle.config.hooks = std::mem::take(&mut self.config.hooks);
+        }
+
+        // Contents of replaced files are not referenced anymore,
+        // while snapshots of files that are still tracked are kept
+        for hash in self.relation.files.values().filter_map(|file| file.hash.as_ref()) {
+            self.objects.release(hash);
+        }
+        for file in self.snapshots.get_files() {
+            if !bundle.relations.files.contains_key(&file) {
+                self.snapshots.remove_file(&mut self.objects, &file);
+            }
+        }
+        // Local contents are same with committed contents of the bundle
+        for file in report.rebased.iter() {
+            self.objects.put(&std::fs::read(file)?)?;
+        }
+
+        self.relation = bundle.relations;
+        self.history = bundle.history;
+        self.config = bundle.config;
+        self.meta = Meta::new();
+
+        self.relation.save_to_file(self.root_path.as_ref())?;
+        self.history.save_to_file(self.root_path.as_ref())?;
+        self.config.save_to_file(self.root_path.as_ref())?;
+        self.meta.save_to_file(self.root_path.as_ref())?;
+        self.snapshots.save_to_file(self.root_path.as_ref())?;
+        self.objects.save_to_file(self.root_path.as_ref())?;
+
+        self.print_rebase_report(&report);
+        println!("Imported {} files from \"{}\"", self.relation.files.len(), path.display());
+        Ok(())
+    }
+
+    /// Rebase stored modified times against files in working tree with content hashes
+    ///
+    /// This is necessary when modified times were reset by e.g. git clone.
+    /// Dry run only prints the result.
+    pub fn rebase_times(&mut self, dry_run: bool) -> Result<(), RifError> {
+        if dry_run {
+            let report = self.relation.clone().rebase_times()?;
+            self.print_rebase_report(&report);
+            return Ok(());
+        }
+
+        let report = self.relation.rebase_times()?;
+        self.relation.save_to_file(self.root_path.as_ref())?;
+        self.print_rebase_report(&report);
+        Ok(())
+    }
+
     /// Show file informations of rif project
     pub fn list(&self, file : Option<impl AsRef<Path>>, list_type: ListType, depth: Option<usize>) -> Result<(), RifError> {
         if let Some(file) = file {
@@ -328,13 +931,83 @@ impl Rif {
                 ListType::Stale => {
                     self.relation.display_stale_files(depth.unwrap_or(0))?;
                 }
-                // Updated is not yet added
-                _ => (),
+                ListType::Updated(since) => {
+                    let files: Vec<PathBuf> = match since {
+                        Some(since) => self.relation.get_updated_files(since),
+                        // Renamed or removed files are not listed
+                        None => self.meta.last_committed
+                            .iter()
+                            .filter(|path| self.relation.files.contains_key(*path))
+                            .cloned()
+                            .collect(),
+                    };
+                    self.relation.display_files_depth(&files, depth.unwrap_or(0))?;
+                }
+                ListType::Modified => {
+                    self.relation.display_files_depth(&self.relation.get_modified_files()?, depth.unwrap_or(0))?;
+                }
+                ListType::Orphan => {
+                    self.relation.display_files_depth(&self.relation.get_orphan_files(), depth.unwrap_or(0))?;
+                }
+                ListType::Leaf => {
+                    self.relation.display_files_depth(&self.relation.get_leaf_files(), depth.unwrap_or(0))?;
+                }
+                ListType::Root => {
+                    self.relation.display_files_depth(&self.relation.get_root_files(), depth.unwrap_or(0))?;
+                }
+                ListType::Query(query) => {
+                    self.relation.display_files_depth(&self.select(&query)?, depth.unwrap_or(0))?;
+                }
             }
         }
         Ok(())
     }
 
+    /// Select files with a query
+    ///
+    /// Selected files are sorted by path. Refer query module for syntax.
+    pub fn select(&self, query: &str) -> Result<Vec<PathBuf>, RifError> {
+        let selected = query::Query::parse(query)?.evaluate(&self.relation)?;
+        Ok(selected.into_iter().sorted().collect())
+    }
+
+    /// Select staged files with a query
+    ///
+    /// Files that are not staged are not selected, thus query never stages files
+    pub fn select_staged_query(&self, query: &str) -> Result<Vec<PathBuf>, RifError> {
+        let staged: HashSet<&PathBuf> = self.meta.to_be_added_later().collect();
+        Ok(self.select(query)?.into_iter().filter(|file| staged.contains(file)).collect())
+    }
+
+    /// Print files selected by a query
+    pub fn query(&self, query: &str) -> Result<(), RifError> {
+        for path in self.select(query)? {
+            println!("{} {}", path.display(), self.relation.files.get(&path).unwrap().status);
+        }
+        Ok(())
+    }
+
+    /// Export reference graph of rif project
+    ///
+    /// If file is given, export subgraph rooted at the file limited by depth.
+    /// If query is given, only selected files are exported.
+    pub fn graph(&self, file: Option<impl AsRef<Path>>, format: GraphFormat, depth: Option<usize>, cluster: bool, query: Option<&str>) -> Result<(), RifError> {
+        let selection: Option<HashSet<PathBuf>> = match query {
+            Some(query) => Some(self.select(query)?.into_iter().collect()),
+            None => None,
+        };
+        let output = graph::export(&self.relation, format, file.as_ref().map(|f| f.as_ref()), depth.unwrap_or(0), cluster, selection.as_ref())?;
+        print!("{}", output);
+        Ok(())
+    }
+
+    /// Generate static html report of rif project into given directory
+    pub fn report(&self, out_dir: &Path) -> Result<(), RifError> {
+        report::generate_html(&self.relation, &self.history, out_dir)?;
+        println!("Generated a report \"{}\"", out_dir.join("index.html").display());
+        Ok(())
+    }
+
     /// Show data of rif project
     pub fn data(&self, data_type: Option<&str>, compact: bool) -> Result<(), RifError> {
         if let Some(data_type) = data_type {
@@ -375,45 +1048,298 @@ impl Rif {
             return Err(RifError::CheckerError("Check with deleted files are illegal. Rejected".to_owned()));
         }
 
-        self.check_exec()?;
+        let changed_files = self.check_exec()?;
+        self.relation.save_to_file(self.root_path.as_ref())?;
+
+        self.trigger_check_hooks(changed_files, None)?;
         Ok(())
     }
 
     /// Check sanity of rif proeject
-    pub fn sanity(&mut self, fix: bool) -> Result<(), RifError> {
+    ///
+    /// Fix prints planned repairs and applies them after backing up rif file.
+    /// Nothing is applied if dry_run is true.
+    pub fn sanity(&mut self, fix: bool, dry_run: bool, policy: CyclePolicy) -> Result<(), RifError> {
         // NOTE ::: You don't have to manually call sanity check
         // Because read operation always check file sanity after reading a file
         // and return erros if sanity was not assured.
         if fix {
-            self.relation.sanity_fix()?;
+            let mut repairs = self.relation.sanity_plan(policy)?;
+            repairs.extend(self.history.sanity_plan(&self.relation));
+            if repairs.is_empty() {
+                println!("Nothing to fix");
+                return Ok(());
+            }
+
+            println!("# Planned repairs :");
+            for repair in repairs.iter() {
+                println!("    {}", repair);
+            }
+            if dry_run {
+                return Ok(());
+            }
+
+            // Backup before applying so that repairs can be undone manually
+            let backup_path = utils::get_rel_backup_path(self.root_path.as_ref())?;
+            std::fs::copy(utils::get_rel_path(self.root_path.as_ref())?, &backup_path)?;
+            let history_backup_path = utils::get_history_backup_path(self.root_path.as_ref())?;
+            std::fs::copy(utils::get_history_path(self.root_path.as_ref())?, &history_backup_path)?;
+
+            self.relation.apply_repairs(&repairs);
+            self.history.apply_repairs(&repairs)?;
+            let mut deleted = false;
+            for repair in repairs.iter() {
+                if let Repair::MarkDeleted(file) = repair {
+                    self.meta.queue_deleted(file);
+                    deleted = true;
+                }
+            }
             self.relation.save_to_file(self.root_path.as_ref())?;
+            self.history.save_to_file(self.root_path.as_ref())?;
+            self.meta.save_to_file(self.root_path.as_ref())?;
+
+            println!("\nBackup was saved to \"{}\" and \"{}\"", backup_path.display(), history_backup_path.display());
+            if deleted {
+                println!("Commit to remove deleted files from rif");
+            }
             println!("Sucessfully fixed the rif file");
         } else {
             self.relation.sanity_check()?;
+            self.history.sanity_check(&self.relation)?;
             println!("Sucessfully checked the rif file");
         }
         Ok(())
     }
 
+    /// Watch file changes and report impact continuously
+    ///
+    /// Modified and deleted files are staged automatically if auto_add is true.
+    /// This method doesn't return unless watcher fails.
+    #[cfg(feature = "watch")]
+    pub fn watch(&mut self, auto_add: bool) -> Result<(), RifError> {
+        let root = if let Some(path) = &self.root_path {
+            path.to_owned()
+        } else {
+            std::env::current_dir()?
+        };
+        // Changed paths are relativized against root directory
+        std::env::set_current_dir(&root)?;
+        println!("Watching \"{}\" for changes", root.display());
+
+        let debounce = std::time::Duration::from_millis(WATCH_DEBOUNCE_MILLIS);
+        watch::watch_directory(&root, debounce, &mut |paths| {
+            // Other rif commands might have changed rif files while watching
+            self.relation = Relations::read_from_file(self.root_path.as_ref())?;
+            self.meta = Meta::read_from_file(self.root_path.as_ref())?;
+            self.report_changes(paths, auto_add)
+        })
+    }
+
+    /// Serve rif status to editors as a language server
+    ///
+    /// Server communicates through stdin and stdout until exit notification is received.
+    /// Hooks are not triggered by operations requested from editors.
+    pub fn lsp(&mut self) -> Result<(), RifError> {
+        let root = if let Some(path) = &self.root_path {
+            path.to_owned()
+        } else {
+            std::env::current_dir()?
+        };
+        // Every file path in rif is relative to root directory
+        std::env::set_current_dir(&root)?;
+
+        lsp::LspServer::new(self, &root).run()
+    }
+
+    /// Browse and edit relations in an interactive terminal ui
+    #[cfg(feature = "tui")]
+    pub fn tui(&mut self) -> Result<(), RifError> {
+        if let Some(path) = &self.root_path {
+            std::env::set_current_dir(path)?;
+        }
+
+        tui::Tui::new(self).run()
+    }
+
     // External methods end
 
     // MISC methods start
     //
     
     /// Check file relations(impact of changes)
-    fn check_exec(&mut self) -> Result<(), RifError> {
+    ///
+    /// Returns files whose statuses were changed, which should be passed to trigger_check_hooks
+    /// after relations are saved.
+    fn check_exec(&mut self) -> Result<Vec<HookFile>, RifError> {
+        let old_statuses: HashMap<PathBuf, FileStatus> = self.relation.files
+            .iter()
+            .map(|(path, file)| (path.to_owned(), file.status))
+            .collect();
+
         // Check relations(impact)
         let mut checker = Checker::with_relations(&self.relation)?;
         let changed_files = checker.check(&mut self.relation)?;
 
-        if changed_files.len() != 0 && self.config.hook.trigger {
-            println!("\nHook Output");
-            self.config.hook.execute(changed_files)?;
+        // Print status changes into stdout
+        for (status, path) in changed_files.iter() {
+            println!("Status update \"{}\" {}", utils::green(&path.display().to_string()), status);
+        }
+
+        Ok(changed_files
+            .iter()
+            .map(|(_, path)| self.hook_file(path, old_statuses.get(path).copied()))
+            .collect())
+    }
+
+    /// Trigger hooks for files whose statuses were changed by check
+    ///
+    /// Message is passed to hooks as a message of the operation
+    fn trigger_check_hooks(&self, changed_files: Vec<HookFile>, message: Option<&str>) -> Result<(), RifError> {
+        if changed_files.is_empty() {
+            return Ok(());
+        }
+
+        let stale_files: Vec<HookFile> = changed_files.iter().filter(|file| file.new_status == FileStatus::Stale).cloned().collect();
+        let fresh_files: Vec<HookFile> = changed_files.iter().filter(|file| file.new_status == FileStatus::Fresh).cloned().collect();
+
+        self.trigger_hooks(HookEvent::PostCheck, changed_files, message)?;
+        if !stale_files.is_empty() {
+            self.trigger_hooks(HookEvent::OnStale, stale_files, message)?;
+        }
+        if !fresh_files.is_empty() {
+            self.trigger_hooks(HookEvent::OnFresh, fresh_files, message)?;
         }
 
         Ok(())
     }
+
+    /// Move history of a renamed file and record the rename
+    fn record_rename(&mut self, source_name: &Path, new_name: &Path) -> Result<(), RifError> {
+        self.history.rename_file(source_name, new_name)?;
+        self.snapshots.rename_file(source_name, new_name);
+        self.history.add_history(new_name, &format!("Renamed from {}", source_name.display()))
+    }
+
+    /// Trigger post rename hooks
+    ///
+    /// Hook receives both source name and new name
+    fn trigger_rename_hooks(&self, source_name: &Path, new_name: &Path) -> Result<(), RifError> {
+        let mut source_file = self.hook_file(new_name, None);
+        source_file.path = source_name.to_owned();
+        let arguments = vec![source_file, self.hook_file(new_name, None)];
+        self.trigger_hooks(HookEvent::PostRename, arguments, None)
+    }
+
+    /// Trigger all hooks registered to given event
+    ///
+    /// Pre event hooks return error when hook command fails
+    fn trigger_hooks(&self, event: HookEvent, files: Vec<HookFile>, message: Option<&str>) -> Result<(), RifError> {
+        let mut hooks = self.config.hooks_of(event).peekable();
+        if hooks.peek().is_none() {
+            return Ok(());
+        }
+
+        let root = if let Some(path) = &self.root_path {
+            path.to_owned()
+        } else {
+            std::env::current_dir()?
+        };
+        let payload = HookPayload {
+            event,
+            message: message.map(|msg| msg.to_owned()),
+            root,
+            files,
+        };
+
+        for hook in hooks {
+            println!("\nHook Output ({:?})", event);
+            hook.execute(&payload)?;
+        }
+        Ok(())
+    }
+
+    /// Create hook payload files from given files
+    fn hook_arguments<'a>(&self, files: impl Iterator<Item = &'a Path>) -> Vec<HookFile> {
+        files.map(|file| self.hook_file(file, None)).collect()
+    }
+
+    /// Create a hook payload file with file's current information
+    ///
+    /// Files that are not tracked yet are considered fresh
+    fn hook_file(&self, file: &Path, old_status: Option<FileStatus>) -> HookFile {
+        if let Some(single_file) = self.relation.files.get(file) {
+            HookFile {
+                path: file.to_owned(),
+                old_status,
+                new_status: single_file.status,
+                references: single_file.references.iter().cloned().sorted().collect(),
+                timestamp: Some(single_file.timestamp),
+                last_modified: Some(single_file.last_modified),
+            }
+        } else {
+            HookFile {
+                path: file.to_owned(),
+                old_status,
+                new_status: FileStatus::Fresh,
+                references: vec![],
+                timestamp: None,
+                last_modified: None,
+            }
+        }
+    }
     
+    /// Report changes of given paths
+    ///
+    /// Used by watch method to print tracked file changes and files that would be stale
+    #[cfg(feature = "watch")]
+    fn report_changes(&mut self, paths: HashSet<PathBuf>, auto_add: bool) -> Result<(), RifError> {
+        let mut changes: Vec<String> = vec![];
+
+        for path in paths.into_iter().sorted() {
+            // Ignore file if any of ancestors is in black list
+            if path.ancestors().any(|ancestor| self.black_list.contains(ancestor)) {
+                continue;
+            }
+
+            if let Some(single_file) = self.relation.files.get(&path) {
+                if !path.exists() {
+                    changes.push(utils::red(&format!("    deleted  : {}", path.display())).to_string());
+                    if auto_add {
+                        self.meta.queue_deleted(&path);
+                    }
+                } else if single_file.is_modified(&path)? {
+                    changes.push(utils::red(&format!("    modified : {}", path.display())).to_string());
+                    // Files that are fresh now but would be stale after commit
+                    for depend in self.relation.find_depends(&path)? {
+                        if let Some(FileStatus::Fresh) = self.relation.files.get(&depend).map(|file| file.status) {
+                            changes.push(format!("      would make stale : {}", utils::yellow(&depend.display().to_string())));
+                        }
+                    }
+                    if auto_add {
+                        self.meta.queue_added(&path, false);
+                    }
+                }
+            } else if path.is_file() {
+                changes.push(format!("    untracked: {}", path.display()));
+            }
+        }
+
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        println!("\n# Changes at {} :", utils::get_current_unix_time());
+        for change in changes {
+            println!("{}", change);
+        }
+
+        if auto_add {
+            self.meta.save_to_file(self.root_path.as_ref())?;
+            println!("Staged changes of tracked files");
+        }
+        Ok(())
+    }
+
     /// Check if given path is inside black_list
     fn is_in_black_list(&self, path: &Path) -> bool {
         // File is in rif ignore
@@ -432,6 +1358,202 @@ impl Rif {
         false
     }
 
+    /// Get commit messages of staged files
+    ///
+    /// Returns a message shared by files and a message of each file.
+    /// Messages of files are written in an editor if edit is set and rendered with configured template.
+    fn get_commit_messages(&self, message: Option<&str>, edit: bool) -> Result<(Option<String>, HashMap<PathBuf, String>), RifError> {
+        // Deleted files don't have history
+        let staged: Vec<(PathBuf, StagedChange)> = self.meta
+            .get_staged()
+            .into_iter()
+            .filter(|(_, change)| *change != StagedChange::Deleted)
+            .collect();
+
+        let mut shared = message.map(|msg| msg.to_owned());
+        let mut file_messages = HashMap::new();
+        if edit {
+            let simulated = self.simulate_commit()?;
+            let to_be_stale = self.get_to_be_stale(&simulated);
+            let mut entries = vec![];
+            for (path, change) in staged.iter() {
+                let dependents = self.get_stale_dependents(&simulated, &to_be_stale, path)?;
+                entries.push((path.to_owned(), *change, dependents));
+            }
+
+            let path = utils::get_commit_message_path(self.root_path.as_ref())?;
+            std::fs::write(&path, message::build_template(&entries, message))?;
+            let edited = message::parse_template(&message::edit(&path)?)?;
+            std::fs::remove_file(&path)?;
+
+            shared = edited.shared;
+            file_messages = edited.files;
+        }
+
+        let mut messages = HashMap::new();
+        let mut missing = vec![];
+        for (path, change) in staged.iter() {
+            match file_messages.get(path).or(shared.as_ref()) {
+                Some(msg) => {
+                    let msg = match &self.config.message.template {
+                        Some(template) => message::render(template, msg, path, *change),
+                        None => msg.to_owned(),
+                    };
+                    messages.insert(path.to_owned(), msg);
+                }
+                None => missing.push(format!("    {}", path.display())),
+            }
+        }
+
+        if self.config.message.required && !missing.is_empty() {
+            return Err(RifError::CommitFail(format!("Commit message is required. Files without message :\n{}", missing.join("\n"))));
+        }
+
+        Ok((shared, messages))
+    }
+
+    /// Select staged files by files or directories
+    ///
+    /// Every given path should match at least one staged file
+    fn select_staged(&self, files: &[impl AsRef<Path>]) -> Result<HashSet<PathBuf>, RifError> {
+        let staged: Vec<PathBuf> = self.meta.to_be_added_later().cloned().collect();
+        let mut selected = HashSet::new();
+        let mut not_staged = vec![];
+        for file in files {
+            let path = file.as_ref();
+            let matched: Vec<&PathBuf> = staged
+                .iter()
+                .filter(|staged| path.to_str() == Some(".") || staged.starts_with(path))
+                .collect();
+            if matched.is_empty() {
+                not_staged.push(format!("    {}", path.display()));
+            }
+            selected.extend(matched.into_iter().cloned());
+        }
+
+        if !not_staged.is_empty() {
+            return Err(RifError::CommitFail(format!("Files are not staged, stage them with \"rif add\" first\n{}", not_staged.join("\n"))));
+        }
+        if selected.is_empty() {
+            return Err(RifError::CommitFail("Nothing to commit".to_owned()));
+        }
+        Ok(selected)
+    }
+
+    /// Print files that were not rebased
+    fn print_rebase_report(&self, report: &RebaseReport) {
+        if !report.modified.is_empty() {
+            println!("# Modified files : (content differs from stored hash)");
+            for path in report.modified.iter() {
+                println!("{}", utils::red(&format!("    modified : {}", path.display())));
+            }
+        }
+        if !report.missing.is_empty() {
+            println!("# Missing files :");
+            for path in report.missing.iter() {
+                println!("{}", utils::red(&format!("    deleted  : {}", path.display())));
+            }
+        }
+        if !report.unknown.is_empty() {
+            println!("# Files without hash : (modified times were kept)");
+            for path in report.unknown.iter() {
+                println!("{}", utils::yellow(&format!("    {}", path.display())));
+            }
+        }
+        println!("Rebased modified times of {} files", report.rebased.len());
+    }
+
+    /// Print staged files
+    ///
+    /// Reason option also prints why each file is staged
+    fn print_staged(&self, reason: bool) -> Result<(), RifError> {
+        println!("# Changes to be commited :");
+        let staged = self.meta.get_staged();
+        if staged.is_empty() {
+            println!("    Nothing is staged");
+        }
+        for (path, change) in staged {
+            let mut format = format!("    {} : {}", change, path.display());
+            if reason {
+                format.push_str(&format!(" ({})", self.staged_reason(&path, change)?));
+            }
+            println!("{}", utils::green(&format));
+        }
+        Ok(())
+    }
+
+    /// Get a reason why a file is staged
+    fn staged_reason(&self, path: &Path, change: StagedChange) -> Result<String, RifError> {
+        let reason = match change {
+            StagedChange::New => "not tracked by rif".to_owned(),
+            StagedChange::Forced => "timestamp is updated without modification".to_owned(),
+            StagedChange::Deleted => "file doesn't exist anymore".to_owned(),
+            StagedChange::Modified => {
+                let file = self.relation.files.get(path);
+                let content_changed = match file.and_then(|file| file.hash.as_ref()) {
+                    Some(hash) => &objects::hash_file(path)? != hash,
+                    // Hash is unknown for files tracked by older versions
+                    None => true,
+                };
+                let since = file.map(|file| file.last_modified.to_string()).unwrap_or_default();
+                if content_changed {
+                    format!("content changed since {}", since)
+                } else {
+                    format!("modified time changed since {}, content is identical", since)
+                }
+            }
+        };
+        Ok(reason)
+    }
+
+    /// Get files that become stale in simulated relations
+    fn get_to_be_stale(&self, simulated: &Relations) -> HashSet<PathBuf> {
+        simulated.files
+            .iter()
+            .filter(|(path, file)| {
+                file.status == FileStatus::Stale
+                    && self.relation.files.get(*path).map(|old| old.status != FileStatus::Stale).unwrap_or(false)
+            })
+            .map(|(path, _)| path.to_owned())
+            .collect()
+    }
+
+    /// Get files that depend on given file and become stale in simulated relations
+    fn get_stale_dependents(&self, simulated: &Relations, to_be_stale: &HashSet<PathBuf>, path: &Path) -> Result<Vec<PathBuf>, RifError> {
+        // Only deleted files can't be found from simulated relations
+        let relations = if simulated.files.contains_key(path) { simulated } else { &self.relation };
+        Ok(relations
+            .find_depends(path)?
+            .into_iter()
+            .filter(|dependent| to_be_stale.contains(dependent))
+            .unique()
+            .sorted()
+            .collect())
+    }
+
+    /// Simulate commit of staged files on a copy of relations
+    ///
+    /// Returned relations are checked thus statuses are the result of commit
+    fn simulate_commit(&self) -> Result<Relations, RifError> {
+        let mut simulated = self.relation.clone();
+        for file in self.meta.to_be_deleted.iter() {
+            simulated.remove_file(file)?;
+        }
+        for file in self.meta.to_be_registerd.iter() {
+            simulated.add_file(file)?;
+        }
+        for file in self.meta.to_be_forced.iter() {
+            simulated.update_filestamp_force(file)?;
+        }
+        for file in self.meta.to_be_added.iter() {
+            simulated.update_filestamp(file)?;
+        }
+
+        let mut checker = Checker::with_relations(&simulated)?;
+        checker.check(&mut simulated)?;
+        Ok(simulated)
+    }
+
     /// Add new file to rif 
     fn add_new_file(&mut self, file: &Path) -> Result<(), RifError> {
         self.meta.to_be_registerd.insert(file.to_owned());
@@ -440,8 +1562,12 @@ impl Rif {
 
     /// Remove new file to rif 
     fn remove_file(&mut self, file: &Path) -> Result<(), RifError> {
+        if let Some(hash) = self.relation.files.get(file).and_then(|single| single.hash.as_ref()) {
+            self.objects.release(hash);
+        }
         self.relation.remove_file(file)?;
         self.history.remove_file(file)?;
+        self.snapshots.remove_file(&mut self.objects, file);
         Ok(())
     }
 
@@ -543,6 +1669,12 @@ impl Rif {
 
     /// Add old file
     fn add_old_file(&mut self, file: &Path, force: bool) -> Result<(), RifError> {
+        // Unchanged file would fail commit
+        if file.exists() && !force && !self.relation.files.get(file).unwrap().is_modified(file)? {
+            println!("\"{}\" is not modified, use (-f or --force) option to force update a file", file.display());
+            return Ok(());
+        }
+
         if file.exists() {
             self.meta.queue_added(file, force);
         } else {
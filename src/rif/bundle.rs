@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::consts::BUNDLE_VERSION;
+use crate::rif::config::Config;
+use crate::rif::history::History;
+use crate::rif::rel::Relations;
+use crate::RifError;
+
+/// Portable bundle of project state
+///
+/// Staged files and snapshots are not included because they are local to a working tree.
+/// Content hashes of files are included in relations.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Bundle {
+    pub version: u32,
+    pub relations: Relations,
+    pub history: History,
+    pub config: Config,
+}
+
+impl Bundle {
+    pub fn new(relations: Relations, history: History, config: Config) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            relations,
+            history,
+            config,
+        }
+    }
+
+    /// Read bundle from a json file
+    pub fn read_from_file(path: &Path) -> Result<Self, RifError> {
+        let bundle: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(RifError::InvalidFormat(format!("Bundle version {} is newer than supported version {}", bundle.version, BUNDLE_VERSION)));
+        }
+        Ok(bundle)
+    }
+
+    /// Convert bundle into a json string
+    pub fn to_json(&self) -> Result<String, RifError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
@@ -2,33 +2,90 @@ use std::path::Path;
 use serde::{ Deserialize, Serialize };
 
 use crate::utils;
-use crate::rif::hook::{HookArgument, Hook};
+use crate::rif::hook::{Hook, HookArgument, HookEvent};
 use crate::RifError;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub(crate) struct Config{ 
-    pub hook: Hook,
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
     pub git_ignore: bool,
+    /// Options of commit messages
+    #[serde(default)]
+    pub message: MessageConfig,
+    /// Keep contents of committed files to show diffs of references
+    #[serde(default)]
+    pub snapshot: bool,
     // Set check after update, or --check flag as default
 }
 
+/// Options of commit messages
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub(crate) struct MessageConfig {
+    /// Template applied to every message of a file
+    ///
+    /// "{message}", "{file}" and "{change}" are substituted e.g. "[{change}] {message}"
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Reject commit when a committed file doesn't have a message
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Single hook of older rif config, which was triggered after check
+#[derive(Deserialize, Debug)]
+struct LegacyHook {
+    trigger: bool,
+    command: Option<String>,
+    arg_type: HookArgument,
+}
+
+impl From<LegacyHook> for Hook {
+    fn from(legacy: LegacyHook) -> Self {
+        Self {
+            event: HookEvent::PostCheck,
+            trigger: legacy.trigger,
+            command: legacy.command,
+            arg_type: legacy.arg_type,
+            working_directory: None,
+            shell: false,
+            timeout: None,
+        }
+    }
+}
+
 impl Config {
     pub fn new() -> Self {
         Self {
-            hook : Hook {
-                trigger: false,
-                command: None,
-                arg_type: HookArgument::None,
-            },
+            hooks : vec![],
             // Default is true
             git_ignore: true,
+            message: MessageConfig::default(),
+            snapshot: false,
         }
     }
 
+    /// Get hooks that are triggered by given event
+    pub fn hooks_of(&self, event: HookEvent) -> impl Iterator<Item = &Hook> {
+        self.hooks.iter().filter(move |hook| hook.event == event && hook.trigger)
+    }
+
     /// Read config from a file
     pub fn read_from_file(path : Option<impl AsRef<Path>>) -> Result<Self, RifError> {
         let path = utils::get_config_path(path)?;
-        Ok(serde_json::from_str( &std::fs::read_to_string(path)?)?)
+        let mut value: serde_json::Value = serde_json::from_str( &std::fs::read_to_string(path)?)?;
+
+        // Convert "hook" of older config into "hooks"
+        let legacy = value.as_object_mut().and_then(|config| config.remove("hook"));
+        let mut config: Self = serde_json::from_value(value)?;
+        if let Some(legacy) = legacy {
+            let hook: LegacyHook = serde_json::from_value(legacy)?;
+            // Hook that was never set is not worth keeping
+            if hook.trigger || hook.command.is_some() {
+                config.hooks.insert(0, hook.into());
+            }
+        }
+        Ok(config)
     }
 
     /// Save config into a file
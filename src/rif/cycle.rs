@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use itertools::Itertools;
+
+use crate::rif::rel::Relations;
+
+/// Find reference cycles of relations
+///
+/// Strongly connected components are found with Tarjan's algorithm and
+/// each component that forms a loop is reported as an explicit path
+/// which starts and ends with the same file, e.g. `[a, b, c, a]`.
+/// Cycles are sorted by their first file.
+pub(crate) fn find_cycles(relations: &Relations) -> Vec<Vec<PathBuf>> {
+    let mut tarjan = Tarjan {
+        relations,
+        index: 0,
+        indices: HashMap::new(),
+        low_links: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        components: vec![],
+    };
+    for path in relations.files.keys().sorted() {
+        if !tarjan.indices.contains_key(path) {
+            tarjan.connect(path);
+        }
+    }
+
+    let mut cycles = vec![];
+    for component in tarjan.components {
+        let start = component.iter().min().unwrap();
+        let is_loop = component.len() > 1 || references(relations, start).any(|reference| reference == start);
+        if is_loop {
+            cycles.push(loop_path(relations, start, &component));
+        }
+    }
+
+    cycles.sort();
+    cycles
+}
+
+/// Display cycle as "a -> b -> a"
+pub(crate) fn display_cycle(cycle: &[PathBuf]) -> String {
+    cycle.iter().map(|path| path.display().to_string()).join(" -> ")
+}
+
+/// Display cycles line by line
+pub(crate) fn display_cycles(cycles: &[Vec<PathBuf>]) -> String {
+    cycles.iter().map(|cycle| format!("    {}", display_cycle(cycle))).join("\n")
+}
+
+/// Iterate references of a file that exist in relations
+///
+/// Dangling references are not part of any cycle
+fn references<'a>(relations: &'a Relations, path: &PathBuf) -> impl Iterator<Item = &'a PathBuf> + 'a {
+    relations.files
+        .get(path)
+        .into_iter()
+        .flat_map(|file| file.references.iter())
+        .filter(move |reference| relations.files.contains_key(*reference))
+}
+
+/// Find the shortest loop path from start file back to itself inside a component
+fn loop_path(relations: &Relations, start: &PathBuf, component: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut previous: HashMap<&PathBuf, &PathBuf> = HashMap::new();
+    let mut queue: VecDeque<&PathBuf> = VecDeque::new();
+    queue.push_back(start);
+
+    while let Some(current) = queue.pop_front() {
+        for reference in references(relations, current).sorted() {
+            if !component.contains(reference) {
+                continue;
+            }
+            if reference == start {
+                // Trace back to start
+                let mut path = vec![start.to_owned()];
+                let mut node = current;
+                while node != start {
+                    path.push(node.to_owned());
+                    node = previous.get(node).unwrap();
+                }
+                path.push(start.to_owned());
+                path.reverse();
+                return path;
+            }
+            if !previous.contains_key(reference) {
+                previous.insert(reference, current);
+                queue.push_back(reference);
+            }
+        }
+    }
+
+    // Every file in a component reaches the others thus this is not reachable
+    vec![start.to_owned(), start.to_owned()]
+}
+
+/// State of Tarjan's strongly connected components algorithm
+struct Tarjan<'a> {
+    relations: &'a Relations,
+    index: usize,
+    indices: HashMap<&'a PathBuf, usize>,
+    low_links: HashMap<&'a PathBuf, usize>,
+    stack: Vec<&'a PathBuf>,
+    on_stack: HashSet<&'a PathBuf>,
+    components: Vec<HashSet<PathBuf>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn connect(&mut self, path: &'a PathBuf) {
+        self.indices.insert(path, self.index);
+        self.low_links.insert(path, self.index);
+        self.index += 1;
+        self.stack.push(path);
+        self.on_stack.insert(path);
+
+        for reference in references(self.relations, path) {
+            if !self.indices.contains_key(reference) {
+                self.connect(reference);
+                let low_link = self.low_links[path].min(self.low_links[reference]);
+                self.low_links.insert(path, low_link);
+            } else if self.on_stack.contains(reference) {
+                let low_link = self.low_links[path].min(self.indices[reference]);
+                self.low_links.insert(path, low_link);
+            }
+        }
+
+        // Path is a root of component
+        if self.low_links[path] == self.indices[path] {
+            let mut component = HashSet::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.insert(member.to_owned());
+                if member == path {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde_json::json;
+
+use crate::models::{FileStatus, GraphFormat};
+use crate::rif::rel::Relations;
+use crate::RifError;
+
+/// Export reference graph as a given format
+///
+/// Edges are directed from a file to its references.
+/// # Args
+///
+/// * `relations` - Relations to export
+/// * `format` - Graph format to export
+/// * `root` - Root file of subgraph, whole graph is exported if none
+/// * `depth` - Maximum count of reference hops from root file, 0 means no limit
+/// * `cluster` - Whether to cluster nodes by directory
+/// * `selection` - Files to export, every file is exported if none
+pub(crate) fn export(relations: &Relations, format: GraphFormat, root: Option<&Path>, depth: usize, cluster: bool, selection: Option<&HashSet<PathBuf>>) -> Result<String, RifError> {
+    let mut nodes = collect_nodes(relations, root, depth)?;
+    if let Some(selection) = selection {
+        nodes.retain(|node| selection.contains(*node));
+    }
+    let edges: Vec<(&PathBuf, &PathBuf)> = nodes
+        .iter()
+        .flat_map(|node| {
+            relations.files.get(*node).unwrap().references
+                .iter()
+                .filter(|reference| nodes.contains(reference))
+                .map(move |reference| (*node, reference))
+        })
+        .sorted()
+        .collect();
+
+    let output = match format {
+        GraphFormat::Dot => to_dot(relations, &nodes, &edges, cluster),
+        GraphFormat::Mermaid => to_mermaid(relations, &nodes, &edges, cluster),
+        GraphFormat::Json => to_json(relations, &nodes, &edges)?,
+    };
+
+    Ok(output)
+}
+
+/// Collect nodes to export sorted by path
+fn collect_nodes<'a>(relations: &'a Relations, root: Option<&Path>, depth: usize) -> Result<Vec<&'a PathBuf>, RifError> {
+    let root = if let Some(root) = root {
+        root
+    } else {
+        return Ok(relations.files.keys().sorted().collect());
+    };
+
+    let (root_key, _) = relations.files.get_key_value(root)
+        .ok_or_else(|| RifError::GetFail(format!("Failed to get file with given path : {}", root.display())))?;
+
+    // Breadth first search from root file
+    let mut visited: HashSet<&PathBuf> = HashSet::new();
+    visited.insert(root_key);
+    let mut current: Vec<&PathBuf> = vec![root_key];
+    let mut hop = 0;
+    while !current.is_empty() && (depth == 0 || hop < depth) {
+        let mut next = vec![];
+        for path in current {
+            for reference in relations.files.get(path).unwrap().references.iter() {
+                if visited.insert(reference) {
+                    next.push(reference);
+                }
+            }
+        }
+        current = next;
+        hop += 1;
+    }
+
+    Ok(visited.into_iter().sorted().collect())
+}
+
+/// Group nodes by their parent directories
+fn group_by_directory<'a>(nodes: &[&'a PathBuf]) -> BTreeMap<String, Vec<&'a PathBuf>> {
+    let mut groups: BTreeMap<String, Vec<&PathBuf>> = BTreeMap::new();
+    for node in nodes {
+        let directory = node.parent().map(|dir| dir.display().to_string()).unwrap_or_default();
+        groups.entry(directory).or_default().push(node);
+    }
+    groups
+}
+
+/// Fill colors of each status
+fn status_color(status: FileStatus) -> &'static str {
+    match status {
+        FileStatus::Fresh => "#b7e4c7",
+        FileStatus::Stale => "#f4a6a6",
+        FileStatus::Neutral => "#a6c8f4",
+    }
+}
+
+fn to_dot(relations: &Relations, nodes: &[&PathBuf], edges: &[(&PathBuf, &PathBuf)], cluster: bool) -> String {
+    let node_line = |node: &PathBuf| {
+        let status = relations.files.get(node).unwrap().status;
+        format!("\"{}\" [style=filled, fillcolor=\"{}\", tooltip=\"{:?}\"];", escape_dot(node), status_color(status), status)
+    };
+
+    let mut output = String::from("digraph rif {\n    node [shape=box];\n");
+    if cluster {
+        for (index, (directory, group)) in group_by_directory(nodes).iter().enumerate() {
+            // Files in root directory are not clustered
+            if directory.is_empty() {
+                for node in group {
+                    output.push_str(&format!("    {}\n", node_line(node)));
+                }
+                continue;
+            }
+            output.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", index, directory.replace('"', "\\\"")));
+            for node in group {
+                output.push_str(&format!("        {}\n", node_line(node)));
+            }
+            output.push_str("    }\n");
+        }
+    } else {
+        for node in nodes {
+            output.push_str(&format!("    {}\n", node_line(node)));
+        }
+    }
+    for (from, to) in edges {
+        output.push_str(&format!("    \"{}\" -> \"{}\";\n", escape_dot(from), escape_dot(to)));
+    }
+    output.push_str("}\n");
+    output
+}
+
+fn to_mermaid(relations: &Relations, nodes: &[&PathBuf], edges: &[(&PathBuf, &PathBuf)], cluster: bool) -> String {
+    // Mermaid ids can't contain special characters, thus index is used as id
+    let id = |node: &PathBuf| format!("n{}", nodes.iter().position(|item| *item == node).unwrap());
+    let node_line = |node: &PathBuf| format!("{}[\"{}\"]", id(node), node.display().to_string().replace('"', "#quot;"));
+
+    let mut output = String::from("graph LR\n");
+    if cluster {
+        for (index, (directory, group)) in group_by_directory(nodes).iter().enumerate() {
+            if directory.is_empty() {
+                for node in group {
+                    output.push_str(&format!("    {}\n", node_line(node)));
+                }
+                continue;
+            }
+            output.push_str(&format!("    subgraph d{}[\"{}\"]\n", index, directory.replace('"', "#quot;")));
+            for node in group {
+                output.push_str(&format!("        {}\n", node_line(node)));
+            }
+            output.push_str("    end\n");
+        }
+    } else {
+        for node in nodes {
+            output.push_str(&format!("    {}\n", node_line(node)));
+        }
+    }
+    for (from, to) in edges {
+        output.push_str(&format!("    {} --> {}\n", id(from), id(to)));
+    }
+
+    for status in [FileStatus::Fresh, FileStatus::Stale, FileStatus::Neutral] {
+        let class = format!("{:?}", status).to_lowercase();
+        output.push_str(&format!("    classDef {} fill:{}\n", class, status_color(status)));
+        let ids = nodes
+            .iter()
+            .filter(|node| relations.files.get(**node).unwrap().status == status)
+            .map(|node| id(node))
+            .join(",");
+        if !ids.is_empty() {
+            output.push_str(&format!("    class {} {}\n", ids, class));
+        }
+    }
+    output
+}
+
+fn to_json(relations: &Relations, nodes: &[&PathBuf], edges: &[(&PathBuf, &PathBuf)]) -> Result<String, RifError> {
+    let nodes: Vec<serde_json::Value> = nodes
+        .iter()
+        .map(|node| {
+            let single_file = relations.files.get(*node).unwrap();
+            json!({
+                "id": node,
+                "status": single_file.status,
+                "directory": node.parent().unwrap_or_else(|| Path::new("")),
+                "timestamp": single_file.timestamp,
+            })
+        })
+        .collect();
+    let edges: Vec<serde_json::Value> = edges
+        .iter()
+        .map(|(from, to)| json!({"from": from, "to": to}))
+        .collect();
+
+    Ok(format!("{}\n", serde_json::to_string_pretty(&json!({"nodes": nodes, "edges": edges}))?))
+}
+
+/// Escape a path to be used as dot id
+fn escape_dot(path: &Path) -> String {
+    path.display().to_string().replace('\\', "\\\\").replace('"', "\\\"")
+}
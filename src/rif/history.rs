@@ -4,11 +4,14 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::collections::HashMap;
 use crate::RifError;
+use crate::models::Repair;
+use crate::rif::rel::Relations;
+use itertools::Itertools;
 
 /// Struct history of rif update messags
 ///
 /// Hisotry stores vector of messages thus can be very large theoritically
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct History {
     hist_map : HashMap::<PathBuf, Vec<String>>,
 }
@@ -64,6 +67,76 @@ impl History {
         Ok(())
     }
 
+    /// Get all history of given file
+    ///
+    /// Histories are ordered from the oldest
+    pub fn get_history(&self, path: &Path) -> Option<&Vec<String>> {
+        self.hist_map.get(path)
+    }
+
+    /// Move history of a file to a new name
+    pub fn rename_file(&mut self, file: &Path, new_name: &Path) -> Result<(), RifError> {
+        if let Some(hist) = self.hist_map.remove(file) {
+            self.hist_map.insert(new_name.to_path_buf(), hist);
+        }
+        Ok(())
+    }
+
+    /// Check if every history belongs to a tracked file
+    pub fn sanity_check(&self, relations: &Relations) -> Result<(), RifError> {
+        let orphans = self.get_orphan_files(relations);
+        if !orphans.is_empty() {
+            let orphans = orphans.iter().map(|path| format!("    {}", path.display())).join("\n");
+            return Err(RifError::InvalidFormat(format!("History of untracked files detected\n{}", orphans)));
+        }
+        Ok(())
+    }
+
+    /// Plan repairs of histories that don't belong to any tracked file
+    ///
+    /// Orphan history is moved to a tracked file only if the file is the only tracked file
+    /// with the same file name and has no history, else it is dropped.
+    pub(crate) fn sanity_plan(&self, relations: &Relations) -> Vec<Repair> {
+        let mut repairs = vec![];
+        for orphan in self.get_orphan_files(relations) {
+            let candidates: Vec<&PathBuf> = relations.files
+                .keys()
+                .filter(|path| path.file_name() == orphan.file_name() && !self.hist_map.contains_key(*path))
+                .collect();
+            // Two orphans can't be moved to the same file
+            let taken = repairs.iter().any(|repair| matches!(repair, Repair::MoveHistory { to, .. } if candidates.first() == Some(&to)));
+            if candidates.len() == 1 && !taken {
+                repairs.push(Repair::MoveHistory { from: orphan.to_owned(), to: candidates[0].to_owned() });
+            } else {
+                repairs.push(Repair::DropHistory(orphan.to_owned()));
+            }
+        }
+        repairs
+    }
+
+    /// Apply planned history repairs
+    ///
+    /// Repairs of relations are ignored
+    pub(crate) fn apply_repairs(&mut self, repairs: &[Repair]) -> Result<(), RifError> {
+        for repair in repairs {
+            match repair {
+                Repair::MoveHistory { from, to } => self.rename_file(from, to)?,
+                Repair::DropHistory(file) => self.remove_file(file)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Get sorted files that have history but are not tracked
+    fn get_orphan_files(&self, relations: &Relations) -> Vec<&PathBuf> {
+        self.hist_map
+            .keys()
+            .filter(|path| !relations.files.contains_key(*path))
+            .sorted()
+            .collect()
+    }
+
     /// Removea file from history
     pub fn remove_file(&mut self, file: &Path) -> Result<(), RifError> {
         self.hist_map.remove(file);
@@ -79,7 +152,8 @@ impl History {
         if let Some(hist) = self.hist_map.get(path) {
             // Iterator should be reverse to print the newest first.
             for item in hist.iter().rev() {
-                println!("  | {}", item);
+                // Messages written in editor can have multiple lines
+                println!("  | {}", item.replace('\n', "\n  | "));
             }
         }
         else {
@@ -1,4 +1,6 @@
 use std::path::PathBuf;
+use std::time::Duration;
+use chrono::NaiveDateTime;
 use crate::utils;
 use serde::{Serialize,Deserialize};
 
@@ -6,62 +8,70 @@ use crate::models::FileStatus;
 use crate::RifError;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
-pub(crate) struct Hook { 
+pub(crate) struct Hook {
+    pub event: HookEvent,
     pub trigger: bool,
     pub command: Option<String>,
     pub arg_type: HookArgument,
+    /// Directory to execute a command, default is current working directory
+    #[serde(default)]
+    pub working_directory: Option<PathBuf>,
+    /// Execute a command through system shell
+    #[serde(default)]
+    pub shell: bool,
+    /// Seconds to wait before killing a command
+    #[serde(default)]
+    pub timeout: Option<u64>,
 }
 
 impl Hook {
-    pub fn execute(&self, arguments :Vec<(FileStatus ,PathBuf)>) -> Result<(), RifError> {
+    /// Execute hook command with given payload
+    ///
+    /// Filtered file paths are passed as arguments, while whole payload is passed to stdin as json
+    /// and summary of payload is passed as environment variables.
+    /// Failure to spawn, non-zero exit and timeout return error, and pre hook's error aborts the operation.
+    pub fn execute(&self, payload: &HookPayload) -> Result<(), RifError> {
         // Don't trigger
         if !self.trigger {
             return Ok(());
         }
 
-        let filtered_args : Vec<String>;
+        let filtered_args : Vec<String> = payload.files
+            .iter()
+            .filter(|file| match self.arg_type {
+                HookArgument::Fresh => file.new_status == FileStatus::Fresh,
+                HookArgument::Stale => file.new_status == FileStatus::Stale,
+                HookArgument::All => true,
+                HookArgument::None => false,
+            })
+            .map(|file| file.path.display().to_string())
+            .collect();
 
-        match self.arg_type {
-            HookArgument::Fresh => {
-                filtered_args = arguments
-                    .into_iter()
-                    .filter(|file| if let FileStatus::Fresh = file.0 {true} else {false} )
-                    .map(|file| 
-                        file.1
-                        .as_path()
-                        .display()
-                        .to_string()
-                    )
-                    .collect();
-            },
-            HookArgument::Stale => {
-                filtered_args = arguments
-                    .into_iter()
-                    .filter(|file| if let FileStatus::Stale = file.0 {true} else {false} )
-                    .map(|file| 
-                        file.1
-                        .as_path()
-                        .display()
-                        .to_string()
-                    )
-                    .collect();
-            },
-            HookArgument::All => {
-                filtered_args = arguments
-                    .into_iter()
-                    .map(|file| 
-                        file.1
-                        .as_path()
-                        .display()
-                        .to_string()
-                    )
-                    .collect();
-            }
-            HookArgument::None => filtered_args = Vec::new(),
+        let mut envs = vec![
+            ("RIF_EVENT", format!("{:?}", payload.event)),
+            ("RIF_ROOT", payload.root.display().to_string()),
+            ("RIF_FILE_COUNT", payload.files.len().to_string()),
+        ];
+        if let Some(message) = &payload.message {
+            envs.push(("RIF_MESSAGE", message.to_owned()));
         }
+        let stdin = serde_json::to_string(payload)?;
 
         if let Some(cmd) = &self.command {
-            utils::cmd(cmd,filtered_args)?;
+            let mut command = utils::build_command(cmd, filtered_args, self.shell);
+            command.envs(envs);
+            if let Some(dir) = &self.working_directory {
+                command.current_dir(dir);
+            }
+
+            let status = utils::run_command(command, Some(stdin), self.timeout.map(Duration::from_secs))?;
+            if !status.success() {
+                let code = status.code().map(|code| code.to_string()).unwrap_or_else(|| String::from("signal"));
+                if self.event.is_pre() {
+                    return Err(RifError::HookError(format!("{:?} hook \"{}\" exited with status {}. Rejected", self.event, cmd, code)));
+                }
+                return Err(RifError::HookError(format!("{:?} hook \"{}\" exited with status {}", self.event, cmd, code)));
+            }
             Ok(())
         } else {
             Err(RifError::ConfigError(String::from("Hook trigger is true but it's command is null")))
@@ -69,6 +79,28 @@ impl Hook {
     }
 }
 
+/// Data passed to hook process's stdin as json
+#[derive(Serialize, Debug)]
+pub(crate) struct HookPayload {
+    pub event: HookEvent,
+    pub message: Option<String>,
+    pub root: PathBuf,
+    pub files: Vec<HookFile>,
+}
+
+/// Information of a single file inside hook payload
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct HookFile {
+    pub path: PathBuf,
+    /// Status before the operation, which is only given when status has been changed
+    pub old_status: Option<FileStatus>,
+    pub new_status: FileStatus,
+    pub references: Vec<PathBuf>,
+    /// Timestamps are not given when file is not tracked
+    pub timestamp: Option<NaiveDateTime>,
+    pub last_modified: Option<NaiveDateTime>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum HookArgument {
     Stale,
@@ -76,3 +108,29 @@ pub enum HookArgument {
     All,
     None
 }
+
+/// Lifecycle event that triggers a hook
+///
+/// Pre events are triggered before an operation and can abort the operation
+/// while others are triggered after an operation.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    PreAdd,
+    PreCommit,
+    PostCommit,
+    /// Check has changed any file's status
+    PostCheck,
+    /// Check has made any file stale
+    OnStale,
+    /// Check has made any file fresh
+    OnFresh,
+    PostRename,
+    PostRemove,
+}
+
+impl HookEvent {
+    /// Whether event can veto an operation
+    pub fn is_pre(&self) -> bool {
+        matches!(self, HookEvent::PreAdd | HookEvent::PreCommit)
+    }
+}
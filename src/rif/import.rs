@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::models::{ImportFormat, LoopBranch};
+use crate::utils;
+use crate::RifError;
+
+/// Reference edge directed from a file to its reference
+pub(crate) type Edge = (PathBuf, PathBuf);
+
+/// Read reference edges from a source
+///
+/// Paths are normalized to be relative to the root directory.
+/// # Args
+///
+/// * `source` - File to read edges from, or a directory to scan for markdown files
+/// * `format` - Format of the source
+/// * `black_list` - Paths to skip while scanning markdown files
+pub(crate) fn read_edges(source: &Path, format: ImportFormat, black_list: &HashSet<PathBuf>) -> Result<Vec<Edge>, RifError> {
+    if !source.exists() {
+        return Err(RifError::GetFail(format!("Import source \"{}\" doesn't exist", source.display())));
+    }
+
+    let edges = match format {
+        ImportFormat::Json => parse_json(&std::fs::read_to_string(source)?)?,
+        ImportFormat::Csv => parse_csv(&std::fs::read_to_string(source)?)?,
+        ImportFormat::Dot => parse_dot(&std::fs::read_to_string(source)?)?,
+        ImportFormat::Markdown => collect_markdown_links(source, black_list)?,
+    };
+
+    Ok(edges
+        .into_iter()
+        .map(|(from, to)| (normalize(&from), normalize(&to)))
+        .collect())
+}
+
+/// Parse json edges
+///
+/// Json can be an array of edges, an object with "edges" array which is exported by graph
+/// command, or an object that maps a file to an array of references.
+/// Each edge is an object with "from" and "to" fields or an array of two paths.
+fn parse_json(content: &str) -> Result<Vec<Edge>, RifError> {
+    let json: Value = serde_json::from_str(content)?;
+    let invalid = |value: &Value| RifError::InvalidFormat(format!("Invalid json edge : {}", value));
+
+    let edges = match &json {
+        Value::Object(map) if map.contains_key("edges") => &map["edges"],
+        Value::Object(map) => {
+            let mut edges = vec![];
+            for (from, references) in map.iter() {
+                let references = references.as_array().ok_or_else(|| invalid(references))?;
+                for reference in references {
+                    let to = reference.as_str().ok_or_else(|| invalid(reference))?;
+                    edges.push((PathBuf::from(from), PathBuf::from(to)));
+                }
+            }
+            return Ok(edges);
+        }
+        _ => &json,
+    };
+
+    let edges = edges.as_array().ok_or_else(|| invalid(edges))?;
+    let mut result = vec![];
+    for edge in edges {
+        let (from, to) = match edge {
+            Value::Object(edge) => (edge.get("from"), edge.get("to")),
+            Value::Array(edge) if edge.len() == 2 => (edge.first(), edge.get(1)),
+            _ => return Err(invalid(edge)),
+        };
+        match (from.and_then(Value::as_str), to.and_then(Value::as_str)) {
+            (Some(from), Some(to)) => result.push((PathBuf::from(from), PathBuf::from(to))),
+            _ => return Err(invalid(edge)),
+        }
+    }
+    Ok(result)
+}
+
+/// Parse csv edges
+///
+/// Each line is "from,to". Empty lines, lines starting with '#' and "from,to" header are ignored.
+fn parse_csv(content: &str) -> Result<Vec<Edge>, RifError> {
+    let mut edges = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.eq_ignore_ascii_case("from,to") {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim().trim_matches('"')).collect();
+        if fields.len() != 2 || fields.iter().any(|field| field.is_empty()) {
+            return Err(RifError::InvalidFormat(format!("Invalid csv edge at line {} : {}", index + 1, line)));
+        }
+        edges.push((PathBuf::from(fields[0]), PathBuf::from(fields[1])));
+    }
+    Ok(edges)
+}
+
+/// Parse dot edges
+///
+/// Only edge statements such as "a" -> "b" -> "c"; are read and others are ignored.
+fn parse_dot(content: &str) -> Result<Vec<Edge>, RifError> {
+    let mut edges = vec![];
+    for (index, line) in content.lines().enumerate() {
+        // Strip attributes and statement terminator
+        let line = line.split('[').next().unwrap().trim().trim_end_matches(';').trim();
+        if !line.contains("->") {
+            continue;
+        }
+
+        let ids: Vec<String> = line.split("->").map(|id| unquote_dot(id.trim())).collect();
+        if ids.iter().any(|id| id.is_empty()) {
+            return Err(RifError::InvalidFormat(format!("Invalid dot edge at line {} : {}", index + 1, line)));
+        }
+        for pair in ids.windows(2) {
+            edges.push((PathBuf::from(&pair[0]), PathBuf::from(&pair[1])));
+        }
+    }
+    Ok(edges)
+}
+
+/// Remove quotes and escapes of a dot id
+fn unquote_dot(id: &str) -> String {
+    if id.len() >= 2 && id.starts_with('"') && id.ends_with('"') {
+        id[1..id.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        id.to_owned()
+    }
+}
+
+/// Collect links between markdown files
+///
+/// Inline links and reference definitions that point to local files are collected.
+/// Links are resolved against the directory of the markdown file,
+/// while links starting with '/' are resolved against the root directory.
+fn collect_markdown_links(source: &Path, black_list: &HashSet<PathBuf>) -> Result<Vec<Edge>, RifError> {
+    let mut files = vec![];
+    if source.is_dir() {
+        let mut closure = |entry_path: PathBuf| -> Result<LoopBranch, RifError> {
+            let striped_path = utils::relativize_path(&entry_path)?;
+            if black_list.contains(&striped_path) {
+                if striped_path.is_dir() {
+                    return Ok(LoopBranch::Exit);
+                }
+                return Ok(LoopBranch::Continue);
+            }
+            if is_markdown(&striped_path) {
+                files.push(striped_path);
+            }
+            Ok(LoopBranch::Continue)
+        };
+        utils::walk_directory_recursive(source, &mut closure)?;
+    } else {
+        files.push(utils::relativize_path(source)?);
+    }
+    files.sort();
+
+    let mut edges = vec![];
+    for file in files {
+        let directory = file.parent().unwrap_or_else(|| Path::new("")).to_owned();
+        for link in markdown_links(&std::fs::read_to_string(&file)?) {
+            let target = match link.strip_prefix('/') {
+                Some(absolute) => PathBuf::from(absolute),
+                None => directory.join(link),
+            };
+            // Anchors inside the same file are not references
+            if normalize(&target) != normalize(&file) {
+                edges.push((file.clone(), target));
+            }
+        }
+    }
+    Ok(edges)
+}
+
+fn is_markdown(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("md") | Some("markdown"))
+}
+
+/// Get local link targets of markdown content
+fn markdown_links(content: &str) -> Vec<String> {
+    let mut links = vec![];
+
+    // Inline links : [text](target "title")
+    let mut rest = content;
+    while let Some(start) = rest.find("](") {
+        rest = &rest[start + 2..];
+        if let Some(end) = rest.find(')') {
+            links.push(rest[..end].to_owned());
+            rest = &rest[end..];
+        }
+    }
+
+    // Reference definitions : [label]: target "title"
+    for line in content.lines() {
+        let line = line.trim_start();
+        if let (true, Some(index)) = (line.starts_with('['), line.find("]:")) {
+            links.push(line[index + 2..].to_owned());
+        }
+    }
+
+    links
+        .into_iter()
+        .filter_map(|link| {
+            let link = link.trim();
+            // Drop title
+            let link = link.split_whitespace().next()?;
+            let link = link.trim_start_matches('<').trim_end_matches('>');
+            // Drop anchor and query
+            let link = link.split(['#', '?']).next()?;
+            if link.is_empty() || link.contains("://") || link.starts_with("mailto:") {
+                return None;
+            }
+            Some(link.replace("%20", " "))
+        })
+        .collect()
+}
+
+/// Normalize a path without accessing file system
+///
+/// "." components are removed and ".." components remove their parents.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                // Parent of ".." can't be removed
+                if normalized.file_name().is_some() {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
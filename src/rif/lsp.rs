@@ -0,0 +1,402 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+use crate::models::FileStatus;
+use crate::rif::Rif;
+use crate::rif::history::History;
+use crate::rif::rel::Relations;
+use crate::RifError;
+
+/// Command names that are executed with workspace/executeCommand
+const COMMAND_ACK: &str = "rif.ack";
+const COMMAND_SET: &str = "rif.set";
+const COMMAND_UNSET: &str = "rif.unset";
+
+/// Json rpc error code for invalid json
+const PARSE_ERROR: i64 = -32700;
+/// Json rpc error code for unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Json rpc error code for failed requests
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Language server that serves rif status to editors
+///
+/// Server communicates through stdin and stdout, thus nothing else should be printed to stdout
+/// while server is running. Rif files are read again on every request because other rif
+/// processes may change them.
+pub(crate) struct LspServer<'a> {
+    rif: &'a mut Rif,
+    root: PathBuf,
+    /// Documents opened in the editor
+    opened: HashSet<PathBuf>,
+    /// Files that diagnostics were published to
+    diagnosed: HashSet<PathBuf>,
+}
+
+impl<'a> LspServer<'a> {
+    pub fn new(rif: &'a mut Rif, root: &Path) -> Self {
+        Self {
+            rif,
+            root: root.to_owned(),
+            opened: HashSet::new(),
+            diagnosed: HashSet::new(),
+        }
+    }
+
+    /// Serve requests until exit notification is received
+    pub fn run(&mut self) -> Result<(), RifError> {
+        let stdin = std::io::stdin();
+        let mut reader = stdin.lock();
+
+        while let Some(message) = read_message(&mut reader)? {
+            let message = match message {
+                Ok(message) => message,
+                Err(err) => {
+                    write_message(&json!({"jsonrpc": "2.0", "id": null, "error": {"code": PARSE_ERROR, "message": format!("Invalid json : {}", err)}}))?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or("").to_owned();
+            let id = message.get("id").cloned();
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+            if method == "exit" {
+                break;
+            }
+
+            match self.handle(&method, &params) {
+                Ok(result) => {
+                    if let Some(id) = id {
+                        match result {
+                            Some(result) => write_message(&json!({"jsonrpc": "2.0", "id": id, "result": result}))?,
+                            None => write_message(&json!({"jsonrpc": "2.0", "id": id, "error": {"code": METHOD_NOT_FOUND, "message": format!("Unknown method : {}", method)}}))?,
+                        }
+                    }
+                }
+                Err(err) => {
+                    if let Some(id) = id {
+                        write_message(&json!({"jsonrpc": "2.0", "id": id, "error": {"code": INTERNAL_ERROR, "message": err.to_string()}}))?;
+                    } else {
+                        self.show_message(&err.to_string())?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a single message
+    ///
+    /// Returns None if method is not supported
+    fn handle(&mut self, method: &str, params: &Value) -> Result<Option<Value>, RifError> {
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": {"openClose": true, "change": 0, "save": true},
+                    "codeLensProvider": {"resolveProvider": false},
+                    "codeActionProvider": true,
+                    "executeCommandProvider": {"commands": [COMMAND_ACK, COMMAND_SET, COMMAND_UNSET]},
+                },
+                "serverInfo": {"name": "rif"},
+            }),
+            "initialized" => {
+                self.publish_diagnostics()?;
+                Value::Null
+            }
+            "shutdown" => Value::Null,
+            "textDocument/didOpen" | "textDocument/didSave" => {
+                if let Some(path) = self.document_path(params) {
+                    self.opened.insert(path);
+                }
+                self.publish_diagnostics()?;
+                Value::Null
+            }
+            "textDocument/didClose" => {
+                if let Some(path) = self.document_path(params) {
+                    self.opened.remove(&path);
+                }
+                Value::Null
+            }
+            "textDocument/codeLens" => {
+                self.reload()?;
+                match self.document_path(params) {
+                    Some(path) => self.code_lenses(&path),
+                    None => json!([]),
+                }
+            }
+            "textDocument/codeAction" => {
+                self.reload()?;
+                match self.document_path(params) {
+                    Some(path) => self.code_actions(&path),
+                    None => json!([]),
+                }
+            }
+            "workspace/executeCommand" => {
+                self.execute_command(params)?;
+                self.publish_diagnostics()?;
+                Value::Null
+            }
+            _ => {
+                // Notifications that are not supported are simply ignored
+                if method.starts_with("$/") || method.starts_with("workspace/did") || method.starts_with("textDocument/did") {
+                    return Ok(Some(Value::Null));
+                }
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(result))
+    }
+
+    /// Read rif files again
+    fn reload(&mut self) -> Result<(), RifError> {
+        self.rif.relation = Relations::read_from_file(Some(&self.root))?;
+        self.rif.history = History::read_from_file(Some(&self.root))?;
+        Ok(())
+    }
+
+    /// Get tracked file path from text document parameter
+    fn document_path(&self, params: &Value) -> Option<PathBuf> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let path = uri_to_path(uri)?;
+        let path = path.strip_prefix(&self.root).ok()?.to_owned();
+        Some(path)
+    }
+
+    /// Publish diagnostics of stale files
+    ///
+    /// Diagnostics of files that became fresh are cleared
+    fn publish_diagnostics(&mut self) -> Result<(), RifError> {
+        self.reload()?;
+
+        let mut diagnosed = HashSet::new();
+        for path in self.rif.relation.files.keys().sorted() {
+            let diagnostics = self.diagnostics(path);
+            if diagnostics.is_empty() && !self.diagnosed.contains(path) {
+                continue;
+            }
+            if !diagnostics.is_empty() {
+                diagnosed.insert(path.to_owned());
+            }
+            write_message(&json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {"uri": path_to_uri(&self.root.join(path)), "diagnostics": diagnostics},
+            }))?;
+        }
+
+        // Clear diagnostics of files that are not tracked anymore
+        for path in self.diagnosed.iter().filter(|path| !self.rif.relation.files.contains_key(*path)) {
+            write_message(&json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {"uri": path_to_uri(&self.root.join(path)), "diagnostics": []},
+            }))?;
+        }
+
+        self.diagnosed = diagnosed;
+        Ok(())
+    }
+
+    /// Create diagnostics that explain why a file is stale
+    fn diagnostics(&self, path: &Path) -> Vec<Value> {
+        let single_file = self.rif.relation.files.get(path).unwrap();
+        if single_file.status != FileStatus::Stale {
+            return vec![];
+        }
+
+        let mut diagnostics = vec![];
+        for ref_item in single_file.references.iter().sorted() {
+            let ref_file = self.rif.relation.files.get(ref_item).unwrap();
+            let message = match ref_file.status {
+                FileStatus::Neutral => continue,
+                FileStatus::Stale => format!("This file is stale because \"{}\" is stale", ref_item.display()),
+                FileStatus::Fresh => {
+                    if single_file.synced >= ref_file.timestamp {
+                        continue;
+                    }
+                    format!("This file is stale because \"{}\" changed at {}", ref_item.display(), ref_file.timestamp)
+                }
+            };
+            diagnostics.push(json!({
+                "range": {"start": {"line": 0, "character": 0}, "end": {"line": 1, "character": 0}},
+                "severity": 2,
+                "source": "rif",
+                "message": message,
+            }));
+        }
+
+        diagnostics
+    }
+
+    /// Create code lenses that list references and dependents of a file
+    fn code_lenses(&self, path: &Path) -> Value {
+        let single_file = if let Some(file) = self.rif.relation.files.get(path) {
+            file
+        } else {
+            return json!([]);
+        };
+
+        let references = single_file.references.iter().map(|p| p.display().to_string()).sorted().join(", ");
+        let dependents = self.rif.relation.files
+            .iter()
+            .filter(|(_, file)| file.references.contains(path))
+            .map(|(p, _)| p.display().to_string())
+            .sorted()
+            .join(", ");
+
+        let range = json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 0}});
+        json!([
+            {"range": range, "command": {"title": format!("References : {}", if references.is_empty() { "none" } else { &references }), "command": ""}},
+            {"range": range, "command": {"title": format!("Dependents : {}", if dependents.is_empty() { "none" } else { &dependents }), "command": ""}},
+        ])
+    }
+
+    /// Create code actions to acknowledge staleness and to set or unset references
+    ///
+    /// Opened documents are suggested as references to set
+    fn code_actions(&self, path: &Path) -> Value {
+        let single_file = if let Some(file) = self.rif.relation.files.get(path) {
+            file
+        } else {
+            return json!([]);
+        };
+        let file = path.display().to_string();
+
+        let mut actions = vec![];
+        if single_file.status == FileStatus::Stale {
+            actions.push(json!({"title": "Acknowledge staleness", "command": COMMAND_ACK, "arguments": [file]}));
+        }
+        for ref_item in single_file.references.iter().sorted() {
+            let reference = ref_item.display().to_string();
+            actions.push(json!({"title": format!("Unset reference \"{}\"", reference), "command": COMMAND_UNSET, "arguments": [file, reference]}));
+        }
+        for opened in self.opened.iter().sorted() {
+            if opened == path || single_file.references.contains(opened) || !self.rif.relation.files.contains_key(opened) {
+                continue;
+            }
+            let reference = opened.display().to_string();
+            actions.push(json!({"title": format!("Set reference \"{}\"", reference), "command": COMMAND_SET, "arguments": [file, reference]}));
+        }
+
+        Value::Array(actions)
+    }
+
+    /// Execute a command requested by editor
+    fn execute_command(&mut self, params: &Value) -> Result<(), RifError> {
+        self.reload()?;
+
+        let command = params["command"].as_str().unwrap_or("");
+        let arguments: Vec<PathBuf> = params["arguments"]
+            .as_array()
+            .map(|args| args.iter().filter_map(|arg| arg.as_str()).map(PathBuf::from).collect())
+            .unwrap_or_default();
+        let file = arguments.first().ok_or_else(|| RifError::CliError(format!("No argument for command : {}", command)))?;
+        let refs: HashSet<PathBuf> = arguments.iter().skip(1).cloned().collect();
+
+        match command {
+            // Status changes are not printed because stdout is used by server
+            COMMAND_ACK => return self.rif.ack_files(&[file], None, true),
+            COMMAND_SET => self.rif.relation.add_reference(file, &refs)?,
+            COMMAND_UNSET => self.rif.relation.remove_reference(file, &refs)?,
+            _ => return Err(RifError::CliError(format!("Unknown command : {}", command))),
+        }
+
+        self.rif.relation.save_to_file(Some(&self.root))?;
+        Ok(())
+    }
+
+    /// Show a message in editor
+    fn show_message(&self, message: &str) -> Result<(), RifError> {
+        write_message(&json!({
+            "jsonrpc": "2.0",
+            "method": "window/showMessage",
+            "params": {"type": 1, "message": message},
+        }))
+    }
+}
+
+/// Read a single message from reader
+///
+/// Returns None when reader is closed. Body that is not a valid json is returned as an error
+/// so that server can reply to it and keep serving.
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Result<Value, serde_json::Error>>, RifError> {
+    let mut content_length: Option<usize> = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse().ok();
+        }
+    }
+
+    let length = content_length.ok_or_else(|| RifError::InvalidFormat(String::from("Message without content length")))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)))
+}
+
+/// Write a single message to stdout
+fn write_message(message: &Value) -> Result<(), RifError> {
+    let body = serde_json::to_string(message)?;
+    let stdout = std::io::stdout();
+    let mut writer = stdout.lock();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Convert file uri into a path
+///
+/// Only percent encoded characters are decoded
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+
+    // Windows path is given as /C:/path
+    if cfg!(target_os = "windows") {
+        return Some(PathBuf::from(decoded.trim_start_matches('/')));
+    }
+    Some(PathBuf::from(decoded))
+}
+
+/// Convert a path into file uri
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    let path = path.display().to_string().replace('\\', "/");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::models::StagedChange;
+use crate::utils;
+use crate::RifError;
+
+/// Header of a section whose message applies to files without their own message
+const SHARED_HEADER: &str = "[*]";
+
+/// Messages written in an editor
+#[derive(Debug)]
+pub(crate) struct EditedMessages {
+    /// Message for files without their own message
+    pub shared: Option<String>,
+    /// Messages for each file
+    pub files: HashMap<PathBuf, String>,
+}
+
+/// Build a commit message template
+///
+/// Each staged file has its own section under a `[path]` header and
+/// a message under `[*]` header applies to files without their own message.
+///
+/// # Args
+///
+/// * `entries` - Staged files with kinds of change and dependents that will become stale
+/// * `message` - Message to fill the shared section with
+pub(crate) fn build_template(entries: &[(PathBuf, StagedChange, Vec<PathBuf>)], message: Option<&str>) -> String {
+    let mut template = String::from(
+        "# Write a commit message under each file's header.\n\
+         # Message under [*] applies to files without their own message.\n\
+         # Lines starting with '#' are ignored.\n\n",
+    );
+    template.push_str(&format!("{}\n{}\n\n", SHARED_HEADER, message.unwrap_or("")));
+
+    for (path, change, dependents) in entries {
+        template.push_str(&format!("[{}]\n# {}\n", path.display(), change.to_string().trim_end()));
+        if !dependents.is_empty() {
+            let dependents = dependents.iter().map(|path| path.display().to_string()).join(", ");
+            template.push_str(&format!("# makes stale : {}\n", dependents));
+        }
+        template.push('\n');
+    }
+    template
+}
+
+/// Parse an edited commit message template
+///
+/// Empty messages are treated as no message.
+pub(crate) fn parse_template(content: &str) -> Result<EditedMessages, RifError> {
+    let mut shared = None;
+    let mut files = HashMap::new();
+    let mut header: Option<&str> = None;
+    let mut lines: Vec<&str> = vec![];
+
+    // Store message of current section
+    let mut flush = |header: Option<&str>, lines: &mut Vec<&str>| {
+        let message = lines.join("\n").trim().to_owned();
+        lines.clear();
+        match header {
+            _ if message.is_empty() => (),
+            Some(SHARED_HEADER) => { shared.replace(message); }
+            Some(header) => {
+                let path = header.trim_start_matches('[').trim_end_matches(']');
+                files.insert(PathBuf::from(path), message);
+            }
+            None => (),
+        }
+    };
+
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            flush(header, &mut lines);
+            header.replace(trimmed);
+            continue;
+        }
+        if header.is_none() {
+            if trimmed.is_empty() {
+                continue;
+            }
+            return Err(RifError::CommitFail(format!("Message \"{}\" is not under any header", trimmed)));
+        }
+        lines.push(line);
+    }
+    flush(header, &mut lines);
+
+    Ok(EditedMessages { shared, files })
+}
+
+/// Open a file with user's editor and read it after the editor exits
+///
+/// Editor is read from VISUAL and EDITOR environment variables in order, vi is used if none is set.
+pub(crate) fn edit(path: &Path) -> Result<String, RifError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+
+    let status = utils::build_command(&editor, vec![path.display().to_string()], true)
+        .status()
+        .map_err(|err| RifError::CommitFail(format!("Failed to execute editor \"{}\" : {}", editor, err)))?;
+    if !status.success() {
+        return Err(RifError::CommitFail(format!("Editor \"{}\" exited with failure. Commit aborted", editor)));
+    }
+
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// Render a message with a template
+///
+/// `{message}`, `{file}` and `{change}` are substituted.
+pub(crate) fn render(template: &str, message: &str, file: &Path, change: StagedChange) -> String {
+    template
+        .replace("{message}", message)
+        .replace("{file}", &file.display().to_string())
+        .replace("{change}", change.to_string().trim_end())
+}
@@ -2,6 +2,8 @@ use serde::{Serialize, Deserialize};
 use std::collections::HashSet;
 use std::path::{PathBuf, Path};
 use crate::RifError;
+use crate::consts::META_FORMAT_VERSION;
+use crate::models::StagedChange;
 use crate::utils;
 
 /// Meta information related to rif directory
@@ -11,6 +13,31 @@ pub(crate) struct Meta {
     pub to_be_added: HashSet<PathBuf>,
     pub to_be_registerd: HashSet<PathBuf>,
     pub to_be_deleted: HashSet<PathBuf>,
+    /// Files updated by the last commit
+    ///
+    /// This is not cleared by clear method. Meta of format version 0 doesn't have it and is read as empty.
+    pub last_committed: HashSet<PathBuf>,
+}
+
+/// Meta of format version 0
+#[derive(Deserialize)]
+struct MetaV0 {
+    to_be_forced: HashSet<PathBuf>,
+    to_be_added: HashSet<PathBuf>,
+    to_be_registerd: HashSet<PathBuf>,
+    to_be_deleted: HashSet<PathBuf>,
+}
+
+impl From<MetaV0> for Meta {
+    fn from(meta: MetaV0) -> Self {
+        Self {
+            to_be_forced: meta.to_be_forced,
+            to_be_added: meta.to_be_added,
+            to_be_registerd: meta.to_be_registerd,
+            to_be_deleted: meta.to_be_deleted,
+            last_committed: HashSet::new(),
+        }
+    }
 }
 
 impl<'a> Meta {
@@ -20,6 +47,7 @@ impl<'a> Meta {
             to_be_forced: HashSet::new(),
             to_be_registerd: HashSet::new(),
             to_be_deleted : HashSet::new(),
+            last_committed : HashSet::new(),
         }
     }
 
@@ -40,12 +68,62 @@ impl<'a> Meta {
         self.to_be_deleted.insert(file.to_owned());
     }
 
-    pub fn remove_add_queue(&mut self, file : &Path) {
-        if self.to_be_added.contains(file) || self.to_be_forced.contains(file) {
-            self.to_be_forced.remove(file);
-            self.to_be_added.remove(file);
-            self.to_be_registerd.remove(file);
-            self.to_be_deleted.remove(file);
+    /// Remove file from every queue
+    ///
+    /// Returns false if file was not queued at all
+    pub fn remove_add_queue(&mut self, file : &Path) -> bool {
+        let forced = self.to_be_forced.remove(file);
+        let added = self.to_be_added.remove(file);
+        let registerd = self.to_be_registerd.remove(file);
+        let deleted = self.to_be_deleted.remove(file);
+        forced || added || registerd || deleted
+    }
+
+    /// Split queued files that are not selected into a new meta
+    ///
+    /// Only selected files are left in the queue
+    pub fn split_unselected(&mut self, selected: &HashSet<PathBuf>) -> Self {
+        let mut unselected = Self::new();
+        let sets = [
+            (&mut self.to_be_added, &mut unselected.to_be_added),
+            (&mut self.to_be_forced, &mut unselected.to_be_forced),
+            (&mut self.to_be_registerd, &mut unselected.to_be_registerd),
+            (&mut self.to_be_deleted, &mut unselected.to_be_deleted),
+        ];
+        for (set, split) in sets {
+            split.extend(set.iter().filter(|path| !selected.contains(*path)).cloned());
+            set.retain(|path| selected.contains(path));
+        }
+        unselected
+    }
+
+    /// Queue files of other meta
+    pub fn merge(&mut self, other: Self) {
+        self.to_be_added.extend(other.to_be_added);
+        self.to_be_forced.extend(other.to_be_forced);
+        self.to_be_registerd.extend(other.to_be_registerd);
+        self.to_be_deleted.extend(other.to_be_deleted);
+    }
+
+    /// Get staged files with kinds of change sorted by path
+    pub fn get_staged(&self) -> Vec<(PathBuf, StagedChange)> {
+        let mut staged: Vec<(PathBuf, StagedChange)> = self.to_be_registerd.iter().map(|path| (path.to_owned(), StagedChange::New))
+            .chain(self.to_be_added.iter().map(|path| (path.to_owned(), StagedChange::Modified)))
+            .chain(self.to_be_forced.iter().map(|path| (path.to_owned(), StagedChange::Forced)))
+            .chain(self.to_be_deleted.iter().map(|path| (path.to_owned(), StagedChange::Deleted)))
+            .collect();
+        staged.sort_by(|a, b| a.0.cmp(&b.0));
+        staged
+    }
+
+    /// Rename queued files
+    pub fn rename_files(&mut self, renames: &[(PathBuf, PathBuf)]) {
+        for set in [&mut self.to_be_added, &mut self.to_be_forced, &mut self.to_be_registerd, &mut self.to_be_deleted, &mut self.last_committed] {
+            for (source, target) in renames {
+                if set.remove(source) {
+                    set.insert(target.to_owned());
+                }
+            }
         }
     }
 
@@ -68,7 +146,13 @@ impl<'a> Meta {
 
     pub fn read_from_file(path: Option<impl AsRef<Path>>) -> Result<Self, RifError> {
         let path = utils::get_meta_path(path)?;
-        let result = bincode::deserialize::<Self>(&std::fs::read(path)?);
+        let bytes = std::fs::read(path)?;
+        let (version, bytes) = utils::split_format_header(&bytes, META_FORMAT_VERSION)?;
+        let result = match version {
+            META_FORMAT_VERSION => bincode::deserialize::<Self>(bytes),
+            // Migrate older format
+            _ => bincode::deserialize::<MetaV0>(bytes).map(Self::from),
+        };
         match result {
             Err(err) => {
                 Err(RifError::BincodeError(err))
@@ -85,7 +169,7 @@ impl<'a> Meta {
         if let Err(err) = result {
             Err(RifError::BincodeError(err))
         } else {
-            std::fs::write(path, result.unwrap())?;
+            std::fs::write(path, utils::add_format_header(META_FORMAT_VERSION, result.unwrap()))?;
             Ok(())
         }
     }
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::consts::OBJECT_COMPRESSION_LEVEL;
+use crate::utils;
+use crate::RifError;
+
+/// Get content hash which is used as a name of an object
+///
+/// Hash is a hex string of blake3 hash
+pub(crate) fn hash_content(content: &[u8]) -> String {
+    blake3::hash(content).to_hex().to_string()
+}
+
+/// Get content hash of a file
+///
+/// Hash is same with a name of the file's object in object store
+pub(crate) fn hash_file(path: &Path) -> Result<String, RifError> {
+    // File is streamed into hasher so that large file is not loaded into memory
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(std::fs::File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Content addressed object store
+///
+/// Objects are zstd compressed contents named after their hashes, thus identical contents are
+/// stored only once. Store keeps the last committed content of each tracked file, which content
+/// hash of relations refers to, and contents of snapshots. Store counts references of each object
+/// and objects without references are pruned by garbage collection.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ObjectStore {
+    /// Directory where objects are saved
+    #[serde(skip)]
+    directory: PathBuf,
+    /// Reference count of each object
+    refs: HashMap<String, usize>,
+}
+
+/// Result of garbage collection
+#[derive(Debug, Default)]
+pub(crate) struct GcReport {
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+impl ObjectStore {
+    /// Store a content and add a reference to it
+    ///
+    /// Returns hash of the content
+    pub fn put(&mut self, content: &[u8]) -> Result<String, RifError> {
+        let hash = hash_content(content);
+        let object = self.directory.join(&hash);
+        if !object.exists() {
+            std::fs::create_dir_all(&self.directory)?;
+            std::fs::write(object, zstd::encode_all(content, OBJECT_COMPRESSION_LEVEL)?)?;
+        }
+        *self.refs.entry(hash.clone()).or_default() += 1;
+        Ok(hash)
+    }
+
+    /// Read a content of an object
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>, RifError> {
+        let object = self.directory.join(hash);
+        if !object.exists() {
+            return Err(RifError::GetFail(format!("Object \"{}\" doesn't exist", hash)));
+        }
+        Ok(zstd::decode_all(&std::fs::read(object)?[..])?)
+    }
+
+    /// Remove a reference to an object
+    ///
+    /// Object itself is removed by garbage collection
+    pub fn release(&mut self, hash: &str) {
+        if let Some(count) = self.refs.get_mut(hash) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Remove objects that are not referenced
+    ///
+    /// Reference counts are replaced by given counts which are collected from users of the store,
+    /// so that counts broken by interrupted operations are also corrected.
+    /// # Args
+    ///
+    /// * `refs` - Reference count of each reachable object
+    /// * `dry_run` - Only report objects to remove
+    pub fn gc(&mut self, refs: HashMap<String, usize>, dry_run: bool) -> Result<GcReport, RifError> {
+        let mut report = GcReport::default();
+        if !self.directory.exists() {
+            return Ok(report);
+        }
+
+        for entry in std::fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let hash = entry.file_name().to_string_lossy().to_string();
+            if refs.get(&hash).copied().unwrap_or(0) > 0 {
+                continue;
+            }
+            report.freed_bytes += entry.metadata()?.len();
+            if !dry_run {
+                std::fs::remove_file(entry.path())?;
+            }
+            report.removed.push(hash);
+        }
+        report.removed.sort();
+
+        if !dry_run {
+            self.refs = refs;
+        }
+        Ok(report)
+    }
+
+    /// Read object store from a file
+    ///
+    /// Projects that never stored an object don't have the file, which is not an error
+    pub fn read_from_file(path: Option<impl AsRef<Path>>) -> Result<Self, RifError> {
+        let directory = utils::get_objects_path(path.as_ref())?;
+        let path = utils::get_object_refs_path(path)?;
+        if !path.exists() {
+            return Ok(Self { directory, refs: HashMap::new() });
+        }
+        let result = bincode::deserialize::<Self>(&std::fs::read(path)?);
+        match result {
+            Err(err) => {
+                Err(RifError::BincodeError(err))
+            }
+            Ok(mut store) => {
+                store.directory = directory;
+                Ok(store)
+            }
+        }
+    }
+
+    /// Save reference counts into a file
+    pub fn save_to_file(&self, path: Option<impl AsRef<Path>>) -> Result<(), RifError> {
+        let result = bincode::serialize(self);
+        let path = utils::get_object_refs_path(path)?;
+        if let Err(err) = result {
+            Err(RifError::BincodeError(err))
+        } else {
+            std::fs::write(path, result.unwrap())?;
+            Ok(())
+        }
+    }
+}
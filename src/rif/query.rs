@@ -0,0 +1,406 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+
+use crate::models::FileStatus;
+use crate::rif::rel::Relations;
+use crate::utils;
+use crate::RifError;
+
+/// Parsed query that selects files from relations
+///
+/// # Syntax
+///
+/// Query is composed of selector functions combined with `&`, `|`, `!` and parentheses.
+/// `!` binds tighter than `&` and `&` binds tighter than `|`.
+///
+/// ```text
+/// stale() & under("docs/") & refs("core.md")
+/// depends_on("core.md", depth=2) | !modified()
+/// depends_on(core.md, depth=2)
+/// ```
+///
+/// Quotes of a path argument can be omitted if the path only contains
+/// alphanumerics, '_', '.', '/' and '-', e.g. `under(2024/notes)`.
+#[derive(Debug)]
+pub(crate) enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Select(Selector),
+}
+
+/// Selector functions available in query
+#[derive(Debug)]
+pub(crate) enum Selector {
+    All,
+    Stale,
+    Fresh,
+    Frozen,
+    Modified,
+    Orphan,
+    Leaf,
+    Root,
+    /// Files updated since given time
+    Updated(NaiveDateTime),
+    /// Files under given directory
+    Under(PathBuf),
+    /// Files that reference given file directly
+    Refs(PathBuf),
+    /// Files that depend on given file within given depth, 0 means no limit
+    DependsOn(PathBuf, usize),
+    /// Files that given file depends on within given depth, 0 means no limit
+    Dependencies(PathBuf, usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(usize),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    Comma,
+    Equal,
+}
+
+/// Argument value given to selector function
+#[derive(Debug)]
+enum Argument {
+    Str(String),
+    Num(usize),
+}
+
+impl Query {
+    /// Parse query from raw string
+    pub fn parse(raw: &str) -> Result<Self, RifError> {
+        let tokens = tokenize(raw)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let query = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(RifError::QueryError(format!("Unexpected token {:?} in query", token)));
+        }
+        Ok(query)
+    }
+
+    /// Evaluate query and get selected files
+    ///
+    /// # Args
+    ///
+    /// * `relations` - Relations to select files from
+    pub fn evaluate(&self, relations: &Relations) -> Result<HashSet<PathBuf>, RifError> {
+        match self {
+            Query::And(lhs, rhs) => {
+                let lhs = lhs.evaluate(relations)?;
+                let rhs = rhs.evaluate(relations)?;
+                Ok(lhs.intersection(&rhs).cloned().collect())
+            }
+            Query::Or(lhs, rhs) => {
+                let lhs = lhs.evaluate(relations)?;
+                let rhs = rhs.evaluate(relations)?;
+                Ok(lhs.union(&rhs).cloned().collect())
+            }
+            Query::Not(query) => {
+                let excluded = query.evaluate(relations)?;
+                Ok(relations.files.keys().filter(|path| !excluded.contains(*path)).cloned().collect())
+            }
+            Query::Select(selector) => selector.evaluate(relations),
+        }
+    }
+}
+
+impl Selector {
+    /// Create a selector from function name and arguments
+    fn new(name: &str, mut args: Vec<Argument>, mut named: HashMap<String, Argument>) -> Result<Self, RifError> {
+        let depth = match named.remove("depth") {
+            Some(Argument::Num(depth)) => Some(depth),
+            Some(Argument::Str(_)) => return Err(RifError::QueryError(String::from("Depth should be an unsigned integer"))),
+            None => None,
+        };
+        if let Some(key) = named.keys().next() {
+            return Err(RifError::QueryError(format!("Unknown argument \"{}\" for {}()", key, name)));
+        }
+
+        let selector = match name {
+            "all" | "stale" | "fresh" | "frozen" | "modified" | "orphan" | "leaf" | "root" => {
+                expect_arguments(name, &args, 0)?;
+                match name {
+                    "all" => Self::All,
+                    "stale" => Self::Stale,
+                    "fresh" => Self::Fresh,
+                    "frozen" => Self::Frozen,
+                    "modified" => Self::Modified,
+                    "orphan" => Self::Orphan,
+                    "leaf" => Self::Leaf,
+                    _ => Self::Root,
+                }
+            }
+            "updated" => {
+                expect_arguments(name, &args, 1)?;
+                Self::Updated(utils::parse_time(&string_argument(args.remove(0)))?)
+            }
+            "under" => {
+                expect_arguments(name, &args, 1)?;
+                Self::Under(PathBuf::from(string_argument(args.remove(0))))
+            }
+            "refs" => {
+                expect_arguments(name, &args, 1)?;
+                Self::Refs(PathBuf::from(string_argument(args.remove(0))))
+            }
+            "depends_on" => {
+                expect_arguments(name, &args, 1)?;
+                Self::DependsOn(PathBuf::from(string_argument(args.remove(0))), depth.unwrap_or(0))
+            }
+            "dependencies" => {
+                expect_arguments(name, &args, 1)?;
+                Self::Dependencies(PathBuf::from(string_argument(args.remove(0))), depth.unwrap_or(0))
+            }
+            _ => return Err(RifError::QueryError(format!("Unknown function : {}()", name))),
+        };
+
+        // Only graph traversals accept depth
+        if depth.is_some() && !matches!(selector, Self::DependsOn(..) | Self::Dependencies(..)) {
+            return Err(RifError::QueryError(format!("{}() doesn't accept depth argument", name)));
+        }
+
+        Ok(selector)
+    }
+
+    fn evaluate(&self, relations: &Relations) -> Result<HashSet<PathBuf>, RifError> {
+        let with_status = |status: FileStatus| -> HashSet<PathBuf> {
+            relations.files
+                .iter()
+                .filter(|(_, file)| file.status == status)
+                .map(|(path, _)| path.to_owned())
+                .collect()
+        };
+
+        let selected = match self {
+            Self::All => relations.files.keys().cloned().collect(),
+            Self::Stale => with_status(FileStatus::Stale),
+            Self::Fresh => with_status(FileStatus::Fresh),
+            Self::Frozen => with_status(FileStatus::Neutral),
+            Self::Modified => relations.get_modified_files()?.into_iter().collect(),
+            Self::Orphan => relations.get_orphan_files().into_iter().collect(),
+            Self::Leaf => relations.get_leaf_files().into_iter().collect(),
+            Self::Root => relations.get_root_files().into_iter().collect(),
+            Self::Updated(since) => relations.get_updated_files(*since).into_iter().collect(),
+            Self::Under(directory) => {
+                // "./docs" and "docs" are the same directory
+                let directory = directory.strip_prefix(".").unwrap_or(directory);
+                relations.files.keys().filter(|path| path.starts_with(directory)).cloned().collect()
+            }
+            Self::Refs(target) => traverse(relations, target, 1, true)?,
+            Self::DependsOn(target, depth) => traverse(relations, target, *depth, true)?,
+            Self::Dependencies(target, depth) => traverse(relations, target, *depth, false)?,
+        };
+
+        Ok(selected)
+    }
+}
+
+/// Traverse relations from a target file and collect visited files except target
+///
+/// # Args
+///
+/// * `target` - File to start traversal
+/// * `depth` - Maximum count of hops, 0 means no limit
+/// * `reverse` - Traverse to dependents if true, else traverse to references
+fn traverse(relations: &Relations, target: &Path, depth: usize, reverse: bool) -> Result<HashSet<PathBuf>, RifError> {
+    if !relations.files.contains_key(target) {
+        return Err(RifError::GetFail(format!("Failed to get file with given path : {}", target.display())));
+    }
+
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    let mut current: Vec<PathBuf> = vec![target.to_owned()];
+    let mut hop = 0;
+    while !current.is_empty() && (depth == 0 || hop < depth) {
+        let mut next = vec![];
+        for path in current {
+            let neighbors: Vec<&PathBuf> = if reverse {
+                relations.files
+                    .iter()
+                    .filter(|(_, file)| file.references.contains(&path))
+                    .map(|(dependent, _)| dependent)
+                    .collect()
+            } else {
+                relations.files.get(&path).unwrap().references.iter().collect()
+            };
+            for neighbor in neighbors {
+                if neighbor != target && visited.insert(neighbor.to_owned()) {
+                    next.push(neighbor.to_owned());
+                }
+            }
+        }
+        current = next;
+        hop += 1;
+    }
+
+    Ok(visited)
+}
+
+fn expect_arguments(name: &str, args: &[Argument], count: usize) -> Result<(), RifError> {
+    if args.len() != count {
+        return Err(RifError::QueryError(format!("{}() takes {} argument(s) but {} were given", name, count, args.len())));
+    }
+    Ok(())
+}
+
+fn string_argument(arg: Argument) -> String {
+    match arg {
+        Argument::Str(value) => value,
+        // Bare path that only has digits e.g. under(2024)
+        Argument::Num(value) => value.to_string(),
+    }
+}
+
+fn tokenize(raw: &str) -> Result<Vec<Token>, RifError> {
+    let mut tokens = vec![];
+    let mut chars = raw.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        let token = match ch {
+            ' ' | '\t' | '\n' | '\r' => continue,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '!' => Token::Not,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '=' => Token::Equal,
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            if let Some(escaped) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        Some(ch) => value.push(ch),
+                        None => return Err(RifError::QueryError(String::from("Unterminated string in query"))),
+                    }
+                }
+                Token::Str(value)
+            }
+            // Bare word is either a number, a function name, an argument key or a path
+            ch if ch.is_alphanumeric() || ch == '_' || ch == '.' || ch == '/' => {
+                let mut value = ch.to_string();
+                while let Some(next) = chars.peek().filter(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '.' | '/' | '-')) {
+                    value.push(*next);
+                    chars.next();
+                }
+                if value.chars().all(|ch| ch.is_ascii_digit()) {
+                    Token::Num(value.parse().map_err(|_| RifError::QueryError(format!("Invalid number : {}", value)))?)
+                } else {
+                    Token::Ident(value)
+                }
+            }
+            _ => return Err(RifError::QueryError(format!("Unexpected character '{}' in query", ch))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser for query
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), RifError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(RifError::QueryError(format!("Expected {:?} but found {:?} in query", expected, token))),
+            None => Err(RifError::QueryError(format!("Expected {:?} but query ended", expected))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Query, RifError> {
+        let mut query = self.parse_and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.next();
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, RifError> {
+        let mut query = self.parse_unary()?;
+        while let Some(Token::And) = self.peek() {
+            self.next();
+            query = Query::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, RifError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let query = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(query)
+            }
+            Some(Token::Ident(name)) => self.parse_call(&name),
+            Some(token) => Err(RifError::QueryError(format!("Unexpected token {:?} in query", token))),
+            None => Err(RifError::QueryError(String::from("Query ended unexpectedly"))),
+        }
+    }
+
+    /// Parse arguments of selector function
+    fn parse_call(&mut self, name: &str) -> Result<Query, RifError> {
+        self.expect(Token::LParen)?;
+        let mut args = vec![];
+        let mut named = HashMap::new();
+
+        if let Some(Token::RParen) = self.peek() {
+            self.next();
+        } else {
+            loop {
+                match self.next() {
+                    Some(Token::Str(value)) => args.push(Argument::Str(value)),
+                    Some(Token::Num(value)) => args.push(Argument::Num(value)),
+                    // Bare word without '=' is a path
+                    Some(Token::Ident(value)) if self.peek() != Some(&Token::Equal) => args.push(Argument::Str(value)),
+                    Some(Token::Ident(key)) => {
+                        self.expect(Token::Equal)?;
+                        let value = match self.next() {
+                            Some(Token::Str(value)) => Argument::Str(value),
+                            Some(Token::Num(value)) => Argument::Num(value),
+                            _ => return Err(RifError::QueryError(format!("Missing value for argument \"{}\"", key))),
+                        };
+                        named.insert(key, value);
+                    }
+                    Some(token) => return Err(RifError::QueryError(format!("Unexpected token {:?} in arguments of {}()", token, name))),
+                    None => return Err(RifError::QueryError(format!("Unclosed arguments of {}()", name))),
+                }
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    _ => return Err(RifError::QueryError(format!("Unclosed arguments of {}()", name))),
+                }
+            }
+        }
+
+        Ok(Query::Select(Selector::new(name, args, named)?))
+    }
+}
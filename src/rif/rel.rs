@@ -1,19 +1,24 @@
 use chrono::NaiveDateTime;
 use std::collections::{ HashMap, HashSet };
 use std::path::{Path, PathBuf};
+use std::io::Write;
 
 // TODO
 // Is this really necessary? Or there could be minimal version?
 // This is for .sorted method
 use itertools::Itertools;
 use serde::{ Serialize, Deserialize };
+use similar::TextDiff;
 use crate::error::RifError;
-use crate::models::{ SanityType, RefStatus, FileStatus};
+use crate::models::{CyclePolicy, DetectedRename, FileStatus, RebaseReport, RenameMatch, Repair};
+use crate::rif::cycle;
+use crate::rif::objects::{hash_file, ObjectStore};
+use crate::consts::{REL_FORMAT_VERSION, RENAME_SIMILARITY, RENAME_SIMILARITY_MAX_BYTES};
 use crate::utils;
 use crate::models::LoopBranch;
 
 /// Relations is a struct that stores all information about rif 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Relations {
     pub files: HashMap<PathBuf, SingleFile>,
 }
@@ -50,7 +55,7 @@ impl Relations {
     /// - > <FILE> <STATUS>
     pub fn display_file(&self, path: &Path) -> String {
         let single_file = self.files.get(path).unwrap();
-        let current_time = single_file.timestamp;
+        let current_time = single_file.synced;
         let mut file_output = String::new();
 
         file_output.push_str(
@@ -60,7 +65,8 @@ impl Relations {
         for ref_item in single_file.references.iter() {
             file_output.push_str(&format!("\n  - > {} {}", ref_item.display(), self.files.get(ref_item).unwrap().status));
             if let FileStatus::Stale = single_file.status {
-                if current_time < self.files.get(ref_item).unwrap().timestamp {
+                let ref_file = self.files.get(ref_item).unwrap();
+                if current_time < ref_file.timestamp && ref_file.status != FileStatus::Neutral {
                     file_output.push_str(&format!("{}", utils::yellow("(u)")));
                 }
             }
@@ -79,6 +85,19 @@ impl Relations {
         Ok(())
     }
 
+    /// Display given files sorted by path
+    ///
+    /// # Args
+    ///
+    /// * `paths` - File paths to display
+    /// * `depth` - Desired depth value to display
+    pub fn display_files_depth(&self, paths: &[PathBuf], depth: usize) -> Result<(), RifError> {
+        for path in paths.iter().sorted() {
+            self.display_file_depth(path, depth)?;
+        }
+        Ok(())
+    }
+
     /// Print a single file's relation 
     ///
     /// # Args
@@ -127,7 +146,7 @@ impl Relations {
     /// * `current_depth` - Current depth in recursion
     fn display_file_recursive(&self, path: &Path, current_depth: usize, indent_level: usize) -> Result<(), RifError> {
         let parent_file = self.files.get(path).unwrap();
-        let current_time = parent_file.timestamp;
+        let current_time = parent_file.synced;
 
         for ref_item_key in parent_file.references.iter() {
             let ref_item = self.files.get(ref_item_key).unwrap();
@@ -136,7 +155,7 @@ impl Relations {
             }
             print!("- > {} {}", ref_item_key.display(), ref_item.status);
             if let FileStatus::Stale = parent_file.status {
-                if current_time < ref_item.timestamp {
+                if current_time < ref_item.timestamp && ref_item.status != FileStatus::Neutral {
                     print!("{}", utils::yellow("(u)"));
                 }
             }
@@ -171,7 +190,7 @@ impl Relations {
             return Err(RifError::AddFail("Invalid file path: Path doesn't exist".to_owned()));
         }
 
-        self.sanity_check_file(file_path, SanityType::Direct)?;
+        self.sanity_check_file(file_path)?;
 
         Ok(true)
     }
@@ -232,6 +251,58 @@ impl Relations {
         Ok(())
     }
 
+    /// Check if given renames can be applied
+    ///
+    /// Every source should be tracked and every target should not be tracked
+    /// unless the target itself is also renamed.
+    /// # Args
+    ///
+    /// * `renames` - Pairs of file path(name) and new name
+    pub fn validate_renames(&self, renames: &[(PathBuf, PathBuf)]) -> Result<(), RifError> {
+        let sources: HashSet<&PathBuf> = renames.iter().map(|(source, _)| source).collect();
+        let mut targets: HashSet<&PathBuf> = HashSet::new();
+        for (source, target) in renames {
+            if !self.files.contains_key(source) {
+                return Err(RifError::RenameFail(format!("\"{}\" is not tracked", source.display())));
+            }
+            if (self.files.contains_key(target) && !sources.contains(target)) || !targets.insert(target) {
+                return Err(RifError::RenameFail(format!("Rename target: \"{}\" already exists", target.display())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rename multiple files at once
+    ///
+    /// Keys and references are rewritten together thus relations are not changed at all
+    /// if any rename is invalid. Unlike rename_file, new paths don't have to exist.
+    /// # Args
+    ///
+    /// * `renames` - Pairs of file path(name) and new name
+    pub fn rename_files(&mut self, renames: &[(PathBuf, PathBuf)]) -> Result<(), RifError> {
+        self.validate_renames(renames)?;
+
+        let renamed: HashMap<&PathBuf, &PathBuf> = renames.iter().map(|(source, target)| (source, target)).collect();
+        let mut files = HashMap::new();
+        for (path, mut file) in std::mem::take(&mut self.files) {
+            file.references = file.references
+                .into_iter()
+                .map(|reference| renamed.get(&reference).map(|target| target.to_path_buf()).unwrap_or(reference))
+                .collect();
+            let path = match renamed.get(&path) {
+                Some(target) => {
+                    file.update_name(target);
+                    target.to_path_buf()
+                }
+                None => path,
+            };
+            files.insert(path, file);
+        }
+        self.files = files;
+
+        Ok(())
+    }
+
     /// Update filestamp of file
     ///
     /// Update file's timestamp and last modified time into file's system last modified time.
@@ -242,14 +313,15 @@ impl Relations {
         if file_path.exists() {
             if let Some(file) = self.files.get_mut(file_path) {
                 // If file is not modified, it should not proceed
-                let system_time = utils::get_file_unix_time(file_path)?;
-                if file.last_modified >= system_time {
+                if !file.is_modified(file_path)? {
                     return Err(RifError::UpdateError(String::from("File is not modified, use (-f or --force) option to force update a file")));
                 }
 
                 let unix_time = utils::get_file_unix_time(file_path)?;
                 file.timestamp = unix_time; 
+                file.synced = unix_time;
                 file.last_modified = unix_time;
+                file.hash = Some(hash_file(file_path)?);
             } else {
                 return Err(RifError::GetFail(String::from("Failed to get file from rif_list")));
             }
@@ -271,7 +343,9 @@ impl Relations {
             if let Some(file) = self.files.get_mut(file_path) {
                 let unix_time = utils::get_current_unix_time();
                 file.timestamp = unix_time; 
+                file.synced = unix_time;
                 file.last_modified = unix_time;
+                file.hash = Some(hash_file(file_path)?);
             } else {
                 return Err(RifError::GetFail(String::from("Failed to get file from rif_list")));
             }
@@ -294,6 +368,7 @@ impl Relations {
                 let unix_time = utils::get_current_unix_time();
                 // Only Update last_modified
                 file.last_modified = unix_time;
+                file.hash = Some(hash_file(file_path)?);
             } else {
                 return Err(RifError::GetFail(String::from("Failed to get file from rif_list")));
             }
@@ -304,6 +379,124 @@ impl Relations {
         Ok(())
     }
 
+    /// Acknowledge a file as reviewed against its current references
+    ///
+    /// This clears stale status without updating file's timestamp, thus files that reference the
+    /// file are not affected. References should not be stale to acknowledge a file.
+    /// # Args
+    ///
+    /// * `file_path` - File path(name) to acknowledge
+    pub fn acknowledge(&mut self, file_path: &Path) -> Result<(), RifError> {
+        let single_file = if let Some(file) = self.files.get(file_path) {
+            file
+        } else {
+            return Err(RifError::GetFail(format!("Failed to get file from rif_list : {}", file_path.display())));
+        };
+
+        // Synced time is the newest timestamp among the file and its references
+        let mut synced = single_file.timestamp;
+        for ref_item in single_file.references.iter() {
+            let ref_file = self.files.get(ref_item).unwrap();
+            if let FileStatus::Stale = ref_file.status {
+                return Err(RifError::UpdateError(format!("Reference \"{}\" is stale. Acknowledge the reference first", ref_item.display())));
+            }
+            if synced < ref_file.timestamp {
+                synced = ref_file.timestamp;
+            }
+        }
+
+        let file = self.files.get_mut(file_path).unwrap();
+        file.synced = synced;
+        file.status = FileStatus::Fresh;
+
+        Ok(())
+    }
+
+    /// Sort files so that references come before files that reference them
+    ///
+    /// References are followed through files that are not given, so indirect references are also
+    /// placed first. Duplicate files are removed.
+    /// # Args
+    ///
+    /// * `files` - Files to sort
+    pub fn sort_by_references(&self, files: &[&Path]) -> Vec<PathBuf> {
+        let selected: HashSet<&Path> = files.iter().copied().collect();
+        let mut visited = HashSet::new();
+        let mut sorted = vec![];
+        for file in files {
+            self.visit_references(file, &selected, &mut visited, &mut sorted);
+        }
+        sorted
+    }
+
+    /// Push selected files in post order of references
+    fn visit_references(&self, file: &Path, selected: &HashSet<&Path>, visited: &mut HashSet<PathBuf>, sorted: &mut Vec<PathBuf>) {
+        if !visited.insert(file.to_owned()) {
+            return;
+        }
+        if let Some(single_file) = self.files.get(file) {
+            for reference in single_file.references.iter().sorted() {
+                self.visit_references(reference, selected, visited, sorted);
+            }
+        }
+        if selected.contains(file) {
+            sorted.push(file.to_owned());
+        }
+    }
+
+    /// Freeze a file
+    ///
+    /// Frozen file is excluded from staleness propagation in both directions.
+    /// # Args
+    ///
+    /// * `file_path` - File path(name) to freeze
+    pub fn freeze(&mut self, file_path: &Path) -> Result<(), RifError> {
+        self.set_file_status(file_path, FileStatus::Neutral)
+    }
+
+    /// Thaw a frozen file
+    ///
+    /// Thawed file is fresh until next check.
+    /// # Args
+    ///
+    /// * `file_path` - File path(name) to thaw
+    pub fn thaw(&mut self, file_path: &Path) -> Result<(), RifError> {
+        if let Some(file) = self.files.get(file_path) {
+            if file.status != FileStatus::Neutral {
+                return Err(RifError::UpdateError(format!("File \"{}\" is not frozen", file_path.display())));
+            }
+        }
+        self.set_file_status(file_path, FileStatus::Fresh)
+    }
+
+    /// Get time when a file became stale
+    ///
+    /// This is the oldest timestamp among updated references or the time
+    /// when a stale reference became stale. Returns none if file is not stale.
+    /// # Args
+    ///
+    /// * `file_path` - File path(name) to get stale time
+    pub fn stale_since(&self, file_path: &Path) -> Option<NaiveDateTime> {
+        let single_file = self.files.get(file_path)?;
+        if single_file.status != FileStatus::Stale {
+            return None;
+        }
+
+        single_file.references
+            .iter()
+            .filter_map(|ref_item| {
+                let ref_file = self.files.get(ref_item)?;
+                match ref_file.status {
+                    FileStatus::Neutral => None,
+                    FileStatus::Stale => self.stale_since(ref_item),
+                    FileStatus::Fresh => {
+                        if ref_file.timestamp > single_file.synced { Some(ref_file.timestamp) } else { None }
+                    }
+                }
+            })
+            .min()
+    }
+
     /// Set references to a file
     ///
     /// This is union operation thus existing files are not affected.
@@ -324,7 +517,19 @@ impl Relations {
         }
 
         if let Some(file) = self.files.get_mut(file_path) {
+            let original = file.references.clone();
             file.references = file.references.union(ref_files).cloned().collect();
+
+            // Reject the whole set if any loop goes through the file
+            let cycles: Vec<Vec<PathBuf>> = cycle::find_cycles(self)
+                .into_iter()
+                .filter(|cycle| cycle.iter().any(|path| path == file_path))
+                .collect();
+            if !cycles.is_empty() {
+                self.files.get_mut(file_path).unwrap().references = original;
+                return Err(RifError::InvalidFormat(format!("Setting references makes infinite reference loop\n{}", cycle::display_cycles(&cycles))));
+            }
+
             self.sanity_check()?;
             Ok(())
         } else {
@@ -369,203 +574,95 @@ impl Relations {
 
     /// Check sanity of rif list
     ///
-    /// Sanity is assured when: file is not referencing itself,
-    /// file referencing conclues to infinite loop
+    /// Sanity is assured when: every file and reference exists,
+    /// no file reference concludes to infinite loop.
+    /// Every loop is reported as an explicit path.
     pub fn sanity_check(&self) -> Result<(), RifError> {
-        for path in self.files.keys() {
-            self.sanity_check_file(path, SanityType::Indirect)?;
+        for path in self.files.keys().sorted() {
+            self.sanity_check_file(path)?;
+            for reference in self.files.get(path).unwrap().references.iter() {
+                if !self.files.contains_key(reference) {
+                    return Err(RifError::InvalidFormat(format!("File \"{}\" is referencing untracked file \"{}\"", path.display(), reference.display())));
+                }
+            }
+        }
+
+        let cycles = cycle::find_cycles(self);
+        if !cycles.is_empty() {
+            return Err(RifError::InvalidFormat(format!("Infinite reference loop detected\n{}", cycle::display_cycles(&cycles))));
         }
         Ok(())
     }
 
     /// Internal function for single file sanity checking
     ///
-    /// This method is used by sanity_check method.
-    /// If sanity type is indirect, only self referncing is checked
-    /// else if sanity type is direct, infnite loop is also checked.
-    /// Infinite loop is detected by following references recursively.
-    /// If recursion finds out repeating file_path then it is infinite loop.
+    /// This checks existence and direct self reference of a file.
+    /// Loops are checked by sanity_check method.
     /// # Args
     ///
     /// * `target_path` - File path to check sanity
-    /// * `sanity_type` - Sanity checking type 
-    fn sanity_check_file(&self, target_path: &Path, sanity_type: SanityType) -> Result<(), RifError> {
-        // NOTE ::: Question - Why sanity type exists?
-        // Answer - |
-        // Because indirect reference checking goes through all files and references which is not
-        // possible in such scenario that only some files were added into rif_list
-        // but direct checking is also needed to early return from easily detectable invalid case.
-        // Thus there exists two types and indirect check also does direct checks
-        
+    fn sanity_check_file(&self, target_path: &Path) -> Result<(), RifError> {
         // Check if file exists in the first place
         if !target_path.exists() {
             return Err(RifError::GetFail(format!("File {} doesn't exist", target_path.display())));
         }
 
         // Check direct self reference.
-        let first_item = self.files.get(target_path).unwrap().references.iter().filter(|a| *a == target_path).next();
-        // If the first exists, then target_path has same file in its reference
-        if let Some(_) = first_item {
+        if self.files.get(target_path).unwrap().references.contains(target_path) {
             return Err(RifError::InvalidFormat(format!("File \"{}\" is referencing itself which is not allowd", target_path.display())));
         }
 
-        // Also check indirect self references.
-        if let SanityType::Indirect = sanity_type {
-            // Recursively check into children and check if the item is same with target_path
-            let current_file = self.files.get(target_path).unwrap();
-
-            // File has no references, then early return
-            if current_file.references.len() == 0 {
-                return Ok(());
-            }
-
-            // Variable for cached status
-            let mut ref_status = RefStatus::Valid;
-
-            // Recursively check
-            for child in self.files.get(target_path).unwrap().references.iter() {
-                self.recursive_check(target_path, child, &mut ref_status)?;
-            }
-
-            if let RefStatus::Invalid = ref_status {
-                return Err(RifError::InvalidFormat(format!("Infinite reference loop detected. Last loop was \"{}\"", target_path.display())));
-            }
-        }
-
         Ok(())
     }
 
-    /// Internal function for sanity checking
+    /// Plan repairs that restore sanity without applying them
     ///
-    /// This method recursively follows file references and find if referencing file is same with parent or ifinite loop is detected.
+    /// Missing files are planned to be marked as deleted instead of being removed
+    /// thus they are removed only when the deletion is committed.
+    /// Loops are broken one edge at a time until no loop is left.
     /// # Args
     ///
-    /// * `origin_path` - Base comparator of recursion. If origin path is detected it is invalid.
-    /// * `current_path` - Current recursion path.
-    /// * `ref_status` - Current status of references; It is either invalid or valid.
-    ///
-    /// # Example
-    ///
-    /// Assume file references are as followed
-    /// `base -> child -> granchild -> base`
-    ///
-    /// On recursion where origin_path is base and current_path is grandchild, it will set ref_status as invalid.
-    /// 
-    fn recursive_check(&self, origin_path: &Path, current_path: &Path, ref_status: &mut RefStatus) -> Result<(), RifError> {
-        // if current path is not existent return erro
-        if !current_path.exists() {
-            return Err(RifError::GetFail(format!("File {} doesn't exist", current_path.display())));
-        }
-
-        if origin_path == current_path {
-            *ref_status = RefStatus::Invalid;
-        } else if let RefStatus::Valid = ref_status {
-            for child in self.files.get(current_path).unwrap().references.iter() {
-                // Current path is same with child which means self referencing 
-                if current_path == child {
-                    return Err(RifError::InvalidFormat(format!("File \"{}\" is referencing itself which is not allowd", current_path.display())));
-                }
+    /// * `policy` - Policy to choose an edge that breaks a loop
+    pub(crate) fn sanity_plan(&self, policy: CyclePolicy) -> Result<Vec<Repair>, RifError> {
+        let mut repairs = vec![];
+        let mut simulated = self.clone();
 
-                self.recursive_check(origin_path, child, ref_status)?;
+        for (path, file) in self.files.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            if !path.exists() {
+                repairs.push(Repair::MarkDeleted(path.to_owned()));
             }
-        }
-
-        Ok(())
-    }
-
-    /// Fix invalid format so that sanity can be retained.
-    ///
-    /// Repeatedly find invalid referecning and fix until sanity check succeeds.
-    pub fn sanity_fix(&mut self) -> Result<(), RifError> {
-        while let Err(_) = self.sanity_check() {
-            for path in self.files.keys().cloned() {
-                if let Ok(Some((parent, child))) = self.sanity_get_invalid(&path) {
-                    if parent == child {
-                        println!("Found invalid {}", parent.display());
-                        self.files.remove(&parent);
-                    } else {
-                        self.files.get_mut(&parent).unwrap().references.remove(&child);
-
-                        // This means invalid reference was caused by absent child file
-                        if !child.exists() {
-                            self.files.remove(&child);
-                        }
-                    }
-                    break;
-                } else {
-                    continue;
+            for reference in file.references.iter().sorted() {
+                if !self.files.contains_key(reference) {
+                    simulated.files.get_mut(path).unwrap().references.remove(reference);
+                    repairs.push(Repair::DropReference { file: path.to_owned(), reference: reference.to_owned() });
                 }
             }
         }
 
-        Ok(())
-    }
-
-    /// Find a invalid reference from rif list 
-    ///
-    /// # Args
-    ///
-    /// * `target_path` - Target file to start sanity checking
-    fn sanity_get_invalid(&self, target_path: &Path) -> Result<Option<(PathBuf, PathBuf)>, RifError> {
-        // If path doesn't exit, it should be "fixed"
-        if !target_path.exists() {
-            return Ok(Some((target_path.to_owned(), target_path.to_owned())));
-        }
-
-        // Check direct self reference.
-        let first_item = self.files.get(target_path).unwrap().references.iter().filter(|a| *a == target_path).next();
-        // If the first exists, then target_path has same file in its reference
-        if let Some(path) = first_item {
-            return Ok(Some((path.clone(), path.clone())));
-        }
-
-        let current_file = self.files.get(target_path).unwrap();
-        // File has no references, then early return
-        if current_file.references.len() == 0 {
-            return Ok(None);
-        }
-
-        // Variable for status cache
-        let mut ref_status = RefStatus::Valid;
-
-        // Recursively check
-        for child in self.files.get(target_path).unwrap().references.iter() {
-            return Ok(self.recursive_find_invalid(target_path, child, &mut ref_status)?);
+        // Breaking an edge doesn't always resolve every loop of a component
+        while let Some(cycle) = cycle::find_cycles(&simulated).into_iter().next() {
+            let (file, reference) = choose_edge(&cycle, policy)?;
+            simulated.files.get_mut(&file).unwrap().references.remove(&reference);
+            repairs.push(Repair::BreakCycle { file, reference, cycle });
         }
 
-        Ok(None)
+        Ok(repairs)
     }
 
-    /// Internal function find invalid reference
-    ///
-    /// This function is used by sanity_get_valid
-    /// Basic logic is very similar to that of recursive_check but it returns invalid reference to caller.
-    /// # Args
+    /// Apply planned repairs
     ///
-    /// * `origin_path` - Base comparator of recursion. If origin path is detected it is invalid.
-    /// * `current_path` - Current recursion path.
-    /// * `ref_status` - Current status of references; It is either invalid or valid.
-    fn recursive_find_invalid(&self, origin_path: &Path, current_path: &Path, ref_status: &mut RefStatus) -> Result<Option<(PathBuf, PathBuf)>, RifError> {
-        // if current path doesn't exit it should be fixed
-        if !current_path.exists() {
-            return Ok(Some((origin_path.to_owned(), current_path.to_owned())));
-        }
-
-        if origin_path == current_path {
-            return Ok(Some((current_path.to_owned(), origin_path.to_owned())));
-        } else if let RefStatus::Valid = ref_status {
-            for child in self.files.get(current_path).unwrap().references.iter() {
-                // Current path is same with child which means self referencing 
-                if current_path == child {
-                    println!("LOG ::: Child is referencing itself");
-                    return Ok(Some((child.clone(), child.clone())));
+    /// Marking deleted files and history repairs are not a part of relations thus they are ignored.
+    pub(crate) fn apply_repairs(&mut self, repairs: &[Repair]) {
+        for repair in repairs {
+            match repair {
+                Repair::DropReference { file, reference } | Repair::BreakCycle { file, reference, .. } => {
+                    if let Some(single_file) = self.files.get_mut(file) {
+                        single_file.references.remove(reference);
+                    }
                 }
-
-                return Ok(self.recursive_find_invalid(origin_path, child, ref_status)?);
+                Repair::MarkDeleted(_) | Repair::MoveHistory { .. } | Repair::DropHistory(_) => (),
             }
         }
-
-        Ok(None)
     }
 
     /// Track and print modified files 
@@ -590,8 +687,7 @@ impl Relations {
                 continue;
             }
 
-            let system_time = utils::get_file_unix_time(path)?;
-            if file.last_modified < system_time {
+            if file.is_modified(path)? {
                 modified.push(path);
             }
         }
@@ -639,8 +735,7 @@ impl Relations {
                 continue;
             }
 
-            let system_time = utils::get_file_unix_time(path)?;
-            if file.last_modified < system_time {
+            if file.is_modified(path)? {
                 modified.push(path.clone());
             }
         }
@@ -648,6 +743,87 @@ impl Relations {
         Ok(modified)
     }
 
+    /// Rebase stored modified times against files in working tree
+    ///
+    /// Modified time of a file is not portable and checkout also resets it.
+    /// File whose content is same with stored hash gets current modified time, so that it is not modified.
+    /// File whose content differs gets modified time older than current one, so that it is modified.
+    /// Timestamps are not changed because they are only compared with each other.
+    pub(crate) fn rebase_times(&mut self) -> Result<RebaseReport, RifError> {
+        let mut report = RebaseReport::default();
+        for (path, file) in self.files.iter_mut().sorted_by(|a, b| a.0.cmp(b.0)) {
+            if !path.exists() {
+                report.missing.push(path.to_owned());
+                continue;
+            }
+            let hash = match &file.hash {
+                Some(hash) => hash,
+                None => {
+                    report.unknown.push(path.to_owned());
+                    continue;
+                }
+            };
+
+            let system_time = utils::get_file_unix_time(path)?;
+            if &hash_file(path)? == hash {
+                file.last_modified = system_time;
+                report.rebased.push(path.to_owned());
+            } else {
+                if file.last_modified >= system_time {
+                    file.last_modified = system_time - chrono::Duration::seconds(1);
+                }
+                report.modified.push(path.to_owned());
+            }
+        }
+        Ok(report)
+    }
+
+    /// Count references to objects from content hashes of files
+    pub fn count_refs(&self) -> HashMap<String, usize> {
+        let mut refs = HashMap::new();
+        for hash in self.files.values().filter_map(|file| file.hash.as_ref()) {
+            *refs.entry(hash.clone()).or_default() += 1;
+        }
+        refs
+    }
+
+    /// Get list of files updated since given time
+    pub fn get_updated_files(&self, since: NaiveDateTime) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.timestamp >= since)
+            .map(|(path, _)| path.to_owned())
+            .collect()
+    }
+
+    /// Get list of files that have no references
+    pub fn get_leaf_files(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.references.is_empty())
+            .map(|(path, _)| path.to_owned())
+            .collect()
+    }
+
+    /// Get list of files that no other file references
+    pub fn get_root_files(&self) -> Vec<PathBuf> {
+        let referenced: HashSet<&PathBuf> = self.files.values().flat_map(|file| file.references.iter()).collect();
+        self.files
+            .keys()
+            .filter(|path| !referenced.contains(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Get list of files that have neither references nor dependents
+    pub fn get_orphan_files(&self) -> Vec<PathBuf> {
+        let roots: HashSet<PathBuf> = self.get_root_files().into_iter().collect();
+        self.get_leaf_files()
+            .into_iter()
+            .filter(|path| roots.contains(path))
+            .collect()
+    }
+
     /// Track and display unregistered files
     ///
     /// Unregistered file is a file which exists in under directory where rif file resides.
@@ -656,6 +832,18 @@ impl Relations {
     ///
     /// * `black_list ` - Blacklists to to ignore when tracking unregistered files
     pub fn track_unregistered_files(&self, black_list: &HashSet<PathBuf>, to_be_registerd: &HashSet<PathBuf>) -> Result<(), RifError> {
+        for path in self.get_unregistered_files(black_list, to_be_registerd)? {
+            println!("    {}", utils::red(&path.display().to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Get list of unregistered files
+    ///
+    /// Logic is very similar to track_unregistered_files but it returns list of unregistered files.
+    pub fn get_unregistered_files(&self, black_list: &HashSet<PathBuf>, to_be_registerd: &HashSet<PathBuf>) -> Result<Vec<PathBuf>, RifError> {
+        let mut unregistered: Vec<PathBuf> = vec![];
         utils::walk_directory_recursive(&std::env::current_dir()?, &mut | walk_path | -> Result<LoopBranch, RifError> {
             let stripped = utils::strip_path(&walk_path, None)?;
             // Path is not in black list else and not to be registered
@@ -663,7 +851,7 @@ impl Relations {
                 if !stripped.is_dir() {
                     // File is not in tracked files
                     if let None = self.files.get(&walk_path) {
-                        println!("    {}", utils::red(&stripped.display().to_string()));
+                        unregistered.push(stripped);
                     }
                 }
                 Ok(LoopBranch::Continue)
@@ -680,7 +868,83 @@ impl Relations {
             }
         })?;
 
-        Ok(())
+        Ok(unregistered)
+    }
+
+    /// Detect renames done outside of rif
+    ///
+    /// Deleted tracked files are matched with unregistered files. Files with identical content hash
+    /// are matched first. Then text files whose contents are similar enough to the last committed
+    /// content in object store are matched from the most similar pair, and the same file name
+    /// breaks ties. Binary files and large files are matched only by content hash.
+    /// Each unregistered file is matched at most once.
+    /// # Args
+    ///
+    /// * `black_list ` - Blacklists to to ignore when finding unregistered files
+    /// * `objects` - Object store to read last committed contents of deleted files
+    pub(crate) fn detect_renames(&self, black_list: &HashSet<PathBuf>, objects: &ObjectStore) -> Result<Vec<DetectedRename>, RifError> {
+        let deleted: Vec<PathBuf> = self.get_deleted_files().into_iter().sorted().collect();
+        if deleted.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Files staged as new files are also candidates
+        let candidates: Vec<PathBuf> = self.get_unregistered_files(black_list, &HashSet::new())?.into_iter().sorted().collect();
+        let mut hashes: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+        for candidate in candidates.iter() {
+            hashes.entry(hash_file(candidate)?).or_default().push(candidate);
+        }
+
+        let mut detected: Vec<DetectedRename> = vec![];
+        let mut used: HashSet<&PathBuf> = HashSet::new();
+        for from in deleted.iter() {
+            let target = self.files.get(from).unwrap().hash
+                .as_ref()
+                .and_then(|hash| hashes.get(hash))
+                .and_then(|paths| paths.iter().find(|path| !used.contains(*path)));
+            if let Some(target) = target {
+                used.insert(target);
+                detected.push(DetectedRename { from: from.to_owned(), to: (*target).to_owned(), matched: RenameMatch::Content });
+            }
+        }
+
+        // Similarity of every pair of remaining text files, contents are read only when needed
+        let mut texts: HashMap<&PathBuf, Option<String>> = HashMap::new();
+        let mut pairs: Vec<(f32, bool, &PathBuf, &PathBuf)> = vec![];
+        for from in deleted.iter() {
+            if detected.iter().any(|rename| &rename.from == from) {
+                continue;
+            }
+            let old = self.files.get(from).unwrap().hash
+                .as_ref()
+                .and_then(|hash| objects.get(hash).ok())
+                .and_then(as_text);
+            let old = match old {
+                Some(old) => old,
+                None => continue,
+            };
+            for candidate in candidates.iter().filter(|candidate| !used.contains(candidate)) {
+                let new = texts.entry(candidate).or_insert_with(|| read_text(candidate));
+                if let Some(new) = new {
+                    let ratio = TextDiff::from_lines(old.as_str(), new.as_str()).ratio();
+                    if ratio >= RENAME_SIMILARITY {
+                        pairs.push((ratio, candidate.file_name() == from.file_name(), from, candidate));
+                    }
+                }
+            }
+        }
+        // Stable sort keeps path order among equally similar pairs
+        pairs.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(b.1.cmp(&a.1)));
+        for (ratio, _, from, to) in pairs {
+            if used.contains(to) || detected.iter().any(|rename| &rename.from == from) {
+                continue;
+            }
+            used.insert(to);
+            detected.push(DetectedRename { from: from.to_owned(), to: to.to_owned(), matched: RenameMatch::Similar(ratio) });
+        }
+
+        detected.sort_by(|a, b| a.from.cmp(&b.from));
+        Ok(detected)
     }
 
     // Ok, what the fuck is happening in here?
@@ -710,7 +974,13 @@ impl Relations {
     /// Read rif file and return rif list
     pub fn read_from_file(path: Option<impl AsRef<Path>>) -> Result<Relations, RifError> {
         let path = utils::get_rel_path(path)?;
-        let result = bincode::deserialize::<Relations>(&std::fs::read(path)?);
+        let bytes = std::fs::read(path)?;
+        let (version, bytes) = utils::split_format_header(&bytes, REL_FORMAT_VERSION)?;
+        let result = match version {
+            REL_FORMAT_VERSION => bincode::deserialize::<Relations>(bytes),
+            // Migrate older formats
+            _ => migrate_files(version, bytes).map(|files| Relations { files }),
+        };
         match result {
             Err(err) => { Err(RifError::BincodeError(err)) }
             Ok(value) => { Ok(value) }
@@ -731,7 +1001,7 @@ impl Relations {
         match result {
             Err(err) => { return Err(RifError::BincodeError(err)); }
             Ok(value) => { 
-                std::fs::write(path, value)?;
+                std::fs::write(path, utils::add_format_header(REL_FORMAT_VERSION, value))?;
             }
         }
         Ok(())
@@ -739,7 +1009,7 @@ impl Relations {
 }
 
 /// Struct that contains information about single file in rif
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SingleFile {
     /// Name of the file, it is not full path
     name: String,
@@ -753,8 +1023,92 @@ pub struct SingleFile {
     ///
     /// This is a critera to compare file status
     pub timestamp: NaiveDateTime,
+    /// Time when the file was last confirmed to be up to date with its references
+    ///
+    /// This is same with timestamp unless the file was acknowledged without modification
+    pub synced: NaiveDateTime,
     /// Files set that contains referencing files
     pub references: HashSet<PathBuf>,
+    /// Content hash of the file when it was last updated
+    ///
+    /// This is used to detect renames done outside of rif
+    pub hash: Option<String>,
+}
+
+/// Single file of rel format version 0
+#[derive(Deserialize)]
+struct SingleFileV0 {
+    name: String,
+    status: FileStatus,
+    last_modified : NaiveDateTime,
+    timestamp: NaiveDateTime,
+    references: HashSet<PathBuf>,
+}
+
+/// Single file of rel format version 1
+#[derive(Deserialize)]
+struct SingleFileV1 {
+    name: String,
+    status: FileStatus,
+    last_modified : NaiveDateTime,
+    timestamp: NaiveDateTime,
+    synced: NaiveDateTime,
+    references: HashSet<PathBuf>,
+}
+
+impl From<SingleFileV0> for SingleFileV1 {
+    fn from(file: SingleFileV0) -> Self {
+        Self {
+            name: file.name,
+            status: file.status,
+            last_modified: file.last_modified,
+            timestamp: file.timestamp,
+            // Older rif didn't distinguish acknowledgement from update
+            synced: file.timestamp,
+            references: file.references,
+        }
+    }
+}
+
+impl SingleFileV1 {
+    /// Migrate into current format
+    ///
+    /// Content hash is taken only from a file that is not modified since it was last updated,
+    /// else hash is unknown and modification is checked by modified time.
+    fn migrate(self, path: &Path) -> SingleFile {
+        let unmodified = utils::get_file_unix_time(path)
+            .map(|system_time| self.last_modified >= system_time)
+            .unwrap_or(false);
+        SingleFile {
+            name: self.name,
+            status: self.status,
+            last_modified: self.last_modified,
+            timestamp: self.timestamp,
+            synced: self.synced,
+            references: self.references,
+            hash: if unmodified { hash_file(path).ok() } else { None },
+        }
+    }
+}
+
+/// Read files of older rel format and migrate them to current format step by step
+///
+/// Migrated files are saved as current format when relations are saved
+fn migrate_files(version: u32, bytes: &[u8]) -> Result<HashMap<PathBuf, SingleFile>, bincode::Error> {
+    let files: HashMap<PathBuf, SingleFileV1> = match version {
+        0 => bincode::deserialize::<HashMap<PathBuf, SingleFileV0>>(bytes)?
+            .into_iter()
+            .map(|(path, file)| (path, file.into()))
+            .collect(),
+        _ => bincode::deserialize(bytes)?,
+    };
+    Ok(files
+        .into_iter()
+        .map(|(path, file)| {
+            let file = file.migrate(&path);
+            (path, file)
+        })
+        .collect())
 }
 
 impl SingleFile {
@@ -765,10 +1119,30 @@ impl SingleFile {
             status: FileStatus::Fresh,
             last_modified: utils::get_current_unix_time(),
             timestamp: utils::get_current_unix_time(),
+            synced: utils::get_current_unix_time(),
+            hash: hash_file(&name).ok(),
             references: HashSet::new()
         }
     }
 
+    /// Check if file is modified since it was last updated
+    ///
+    /// Modified time can be changed without modification e.g. by git checkout,
+    /// thus file with newer modified time is modified only if its content differs from stored hash.
+    /// Files without hash are checked with modified time only.
+    /// # Args
+    ///
+    /// * `path` - Path of the file
+    pub fn is_modified(&self, path: &Path) -> Result<bool, RifError> {
+        if self.last_modified >= utils::get_file_unix_time(path)? {
+            return Ok(false);
+        }
+        match &self.hash {
+            Some(hash) => Ok(&hash_file(path)? != hash),
+            None => Ok(true),
+        }
+    }
+
     /// Update single file structs name to new one
     ///
     /// # Args
@@ -778,3 +1152,56 @@ impl SingleFile {
         self.name = new_name.file_name().unwrap().to_str().unwrap().to_owned();
     }
 }
+
+/// Read a file as text for similarity
+///
+/// Large files and binary files are not compared thus None is returned
+fn read_text(path: &Path) -> Option<String> {
+    let size = std::fs::metadata(path).ok()?.len();
+    if size > RENAME_SIMILARITY_MAX_BYTES {
+        return None;
+    }
+    as_text(std::fs::read(path).ok()?)
+}
+
+/// Convert content into text if it is small enough and not binary
+fn as_text(content: Vec<u8>) -> Option<String> {
+    if content.len() as u64 > RENAME_SIMILARITY_MAX_BYTES || content.contains(&0) {
+        return None;
+    }
+    String::from_utf8(content).ok()
+}
+
+/// Choose an edge of a loop to break
+///
+/// # Args
+///
+/// * `cycle` - Loop path that starts and ends with the same file
+/// * `policy` - Policy to choose an edge
+fn choose_edge(cycle: &[PathBuf], policy: CyclePolicy) -> Result<(PathBuf, PathBuf), RifError> {
+    let edges: Vec<(&PathBuf, &PathBuf)> = cycle.iter().tuple_windows().collect();
+    let index = match policy {
+        CyclePolicy::Last => edges.len() - 1,
+        CyclePolicy::Interactive => {
+            println!("Infinite reference loop : {}", cycle::display_cycle(cycle));
+            for (index, (file, reference)) in edges.iter().enumerate() {
+                println!("    [{}] {} -> {}", index + 1, file.display(), reference.display());
+            }
+            loop {
+                print!("Edge to break [1-{}] : ", edges.len());
+                std::io::stdout().flush()?;
+                let mut input = String::new();
+                if std::io::stdin().read_line(&mut input)? == 0 {
+                    return Err(RifError::CliError(String::from("No edge was chosen to break a loop")));
+                }
+                match input.trim().parse::<usize>() {
+                    Ok(choice) if choice >= 1 && choice <= edges.len() => break choice - 1,
+                    _ => println!("Invalid choice"),
+                }
+            }
+        }
+    };
+
+    let (file, reference) = edges[index];
+    Ok((file.to_owned(), reference.to_owned()))
+}
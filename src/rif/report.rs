@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::models::FileStatus;
+use crate::rif::history::History;
+use crate::rif::rel::Relations;
+use crate::utils;
+use crate::RifError;
+
+/// Directory name for per file pages inside report directory
+const FILES_DIRECTORY: &str = "files";
+
+/// Style sheet embedded in every page so that report is self-contained
+const STYLE: &str = "body{font-family:sans-serif;margin:2em auto;max-width:60em;color:#222}\
+table{border-collapse:collapse;width:100%}th,td{border-bottom:1px solid #ddd;padding:.4em;text-align:left}\
+.fresh{color:#2d6a4f}.stale{color:#c0392b;font-weight:bold}.neutral{color:#2e6fba}\
+ul.history{list-style:none;padding-left:0}ul.history li{border-left:3px solid #ddd;padding-left:.6em;margin:.3em 0}";
+
+/// Generate static html report of rif project
+///
+/// Index page lists stale files sorted by how long they have been stale and
+/// each tracked file gets its own page.
+/// # Args
+///
+/// * `relations` - Relations to report
+/// * `history` - History of files
+/// * `out_dir` - Directory to write report into
+pub(crate) fn generate_html(relations: &Relations, history: &History, out_dir: &Path) -> Result<(), RifError> {
+    std::fs::create_dir_all(out_dir.join(FILES_DIRECTORY))?;
+    let now = utils::get_current_unix_time();
+
+    std::fs::write(out_dir.join("index.html"), index_page(relations, now))?;
+    for path in relations.files.keys() {
+        std::fs::write(out_dir.join(FILES_DIRECTORY).join(page_name(path)), file_page(relations, history, path, now))?;
+    }
+
+    Ok(())
+}
+
+fn index_page(relations: &Relations, now: NaiveDateTime) -> String {
+    let count = |status: FileStatus| relations.files.values().filter(|file| file.status == status).count();
+    let mut body = format!(
+        "<h1>Rif report</h1>\n<p>Generated at {}. {} files, <span class=\"stale\">{} stale</span>, {} fresh, {} frozen.</p>\n",
+        now, relations.files.len(), count(FileStatus::Stale), count(FileStatus::Fresh), count(FileStatus::Neutral)
+    );
+
+    // Stale files that have been stale the longest come first
+    let stale_files: Vec<(&PathBuf, Option<NaiveDateTime>)> = relations.files
+        .iter()
+        .filter(|(_, file)| file.status == FileStatus::Stale)
+        .map(|(path, _)| (path, relations.stale_since(path)))
+        .sorted_by(|a, b| a.1.unwrap_or(now).cmp(&b.1.unwrap_or(now)).then(a.0.cmp(b.0)))
+        .collect();
+
+    body.push_str("<h2>Stale files</h2>\n");
+    if stale_files.is_empty() {
+        body.push_str("<p>All files are up to date.</p>\n");
+    } else {
+        body.push_str("<table>\n<tr><th>File</th><th>Stale since</th><th>Stale for</th></tr>\n");
+        for (path, since) in stale_files {
+            let (since, duration) = match since {
+                Some(since) => (since.to_string(), format_duration(now - since)),
+                None => (String::from("unknown"), String::from("unknown")),
+            };
+            body.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n", file_link(path, FILES_DIRECTORY), since, duration));
+        }
+        body.push_str("</table>\n");
+    }
+
+    body.push_str("<h2>All files</h2>\n<table>\n<tr><th>File</th><th>Status</th><th>Timestamp</th></tr>\n");
+    for (path, file) in relations.files.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+        body.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n", file_link(path, FILES_DIRECTORY), status_label(file.status), file.timestamp));
+    }
+    body.push_str("</table>\n");
+
+    page("Rif report", &body)
+}
+
+fn file_page(relations: &Relations, history: &History, path: &Path, now: NaiveDateTime) -> String {
+    let single_file = relations.files.get(path).unwrap();
+    let mut body = format!("<p><a href=\"../index.html\">Index</a></p>\n<h1>{} {}</h1>\n", escape(&path.display().to_string()), status_label(single_file.status));
+
+    body.push_str("<table>\n");
+    body.push_str(&format!("<tr><th>Timestamp</th><td>{}</td></tr>\n", single_file.timestamp));
+    body.push_str(&format!("<tr><th>Last synced</th><td>{}</td></tr>\n", single_file.synced));
+    if let Some(since) = relations.stale_since(path) {
+        body.push_str(&format!("<tr><th>Stale since</th><td>{} ({})</td></tr>\n", since, format_duration(now - since)));
+    }
+    body.push_str("</table>\n");
+
+    body.push_str("<h2>References</h2>\n");
+    let references: Vec<&PathBuf> = single_file.references.iter().sorted().collect();
+    body.push_str(&file_list(relations, &references, Some(single_file.synced)));
+
+    body.push_str("<h2>Dependents</h2>\n");
+    let dependents: Vec<&PathBuf> = relations.files
+        .iter()
+        .filter(|(_, file)| file.references.contains(path))
+        .map(|(dependent, _)| dependent)
+        .sorted()
+        .collect();
+    body.push_str(&file_list(relations, &dependents, None));
+
+    body.push_str("<h2>History</h2>\n");
+    match history.get_history(path) {
+        Some(items) if !items.is_empty() => {
+            body.push_str("<ul class=\"history\">\n");
+            // Print the newest first
+            for item in items.iter().rev() {
+                let item = if item.is_empty() { String::from("<i>No message</i>") } else { escape(item) };
+                body.push_str(&format!("<li>{}</li>\n", item));
+            }
+            body.push_str("</ul>\n");
+        }
+        _ => body.push_str("<p>No history</p>\n"),
+    }
+
+    page(&path.display().to_string(), &body)
+}
+
+/// Create a list of linked files
+///
+/// Files that are newer than given time are marked as updated
+fn file_list(relations: &Relations, files: &[&PathBuf], time: Option<NaiveDateTime>) -> String {
+    if files.is_empty() {
+        return String::from("<p>None</p>\n");
+    }
+
+    let mut list = String::from("<ul>\n");
+    for path in files {
+        let file = relations.files.get(*path).unwrap();
+        let updated = match time {
+            Some(time) if file.timestamp > time => " (updated)",
+            _ => "",
+        };
+        list.push_str(&format!("<li>{} {}{}</li>\n", file_link(path, "."), status_label(file.status), updated));
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title), STYLE, body
+    )
+}
+
+fn status_label(status: FileStatus) -> &'static str {
+    match status {
+        FileStatus::Fresh => "<span class=\"fresh\">fresh</span>",
+        FileStatus::Stale => "<span class=\"stale\">stale</span>",
+        FileStatus::Neutral => "<span class=\"neutral\">frozen</span>",
+    }
+}
+
+fn file_link(path: &Path, directory: &str) -> String {
+    format!("<a href=\"{}/{}\">{}</a>", directory, page_name(path), escape(&path.display().to_string()))
+}
+
+/// Convert a file path into a unique page name
+///
+/// Characters other than alphanumerics, dash and dot are converted into hex codes
+fn page_name(path: &Path) -> String {
+    let mut name = String::new();
+    for byte in path.display().to_string().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' => name.push(byte as char),
+            _ => name.push_str(&format!("_{:02x}", byte)),
+        }
+    }
+    name.push_str(".html");
+    name
+}
+
+fn format_duration(duration: chrono::Duration) -> String {
+    let days = duration.num_days();
+    let hours = duration.num_hours() - days * 24;
+    if days > 0 {
+        format!("{} days {} hours", days, hours)
+    } else if hours > 0 {
+        format!("{} hours", hours)
+    } else {
+        format!("{} minutes", duration.num_minutes())
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
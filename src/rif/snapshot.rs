@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use serde::{Serialize, Deserialize};
+use similar::{ChangeTag, TextDiff};
+
+use crate::rif::objects::{hash_content, ObjectStore};
+use crate::utils;
+use crate::RifError;
+
+/// Content of a file at a commit
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Snapshot {
+    /// Timestamp of the file when snapshot was taken
+    pub timestamp: NaiveDateTime,
+    /// Content hash, which is also a name of the object
+    pub hash: String,
+}
+
+/// Snapshots of committed file contents
+///
+/// Contents are saved in object store, thus identical contents are stored only once.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Snapshots {
+    files: HashMap<PathBuf, Vec<Snapshot>>,
+}
+
+impl Snapshots {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+        }
+    }
+
+    /// Take a snapshot of current file content
+    ///
+    /// Snapshot is not added if content is same with the latest snapshot
+    /// # Args
+    ///
+    /// * `objects` - Object store to save content
+    /// * `path` - File to take a snapshot of
+    /// * `timestamp` - Timestamp of the file after commit
+    pub fn take(&mut self, objects: &mut ObjectStore, path: &Path, timestamp: NaiveDateTime) -> Result<(), RifError> {
+        let content = std::fs::read(path)?;
+        let snapshots = self.files.entry(path.to_owned()).or_default();
+        if snapshots.last().map(|last| last.hash == hash_content(&content)).unwrap_or(false) {
+            return Ok(());
+        }
+
+        let hash = objects.put(&content)?;
+        snapshots.push(Snapshot { timestamp, hash });
+        Ok(())
+    }
+
+    /// Get the latest snapshot of a file taken at or before given time
+    pub fn get_snapshot_at(&self, path: &Path, time: NaiveDateTime) -> Option<&Snapshot> {
+        self.files
+            .get(path)?
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.timestamp <= time)
+    }
+
+    /// Read content of a snapshot
+    pub fn read_content(&self, objects: &ObjectStore, snapshot: &Snapshot) -> Result<Vec<u8>, RifError> {
+        objects.get(&snapshot.hash)
+    }
+
+    /// Rename snapshots of a file
+    pub fn rename_file(&mut self, file: &Path, new_name: &Path) {
+        if let Some(snapshots) = self.files.remove(file) {
+            self.files.insert(new_name.to_owned(), snapshots);
+        }
+    }
+
+    /// Remove snapshots of a file
+    ///
+    /// References to objects are released and objects are pruned by garbage collection
+    pub fn remove_file(&mut self, objects: &mut ObjectStore, file: &Path) {
+        for snapshot in self.files.remove(file).unwrap_or_default() {
+            objects.release(&snapshot.hash);
+        }
+    }
+
+    /// Get files that have snapshots
+    pub fn get_files(&self) -> Vec<PathBuf> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// Count references to objects from snapshots
+    pub fn count_refs(&self) -> HashMap<String, usize> {
+        let mut refs = HashMap::new();
+        for snapshot in self.files.values().flatten() {
+            *refs.entry(snapshot.hash.clone()).or_default() += 1;
+        }
+        refs
+    }
+
+    /// Read snapshots from a file
+    ///
+    /// Projects that never took snapshots don't have the file, which is not an error
+    pub fn read_from_file(path: Option<impl AsRef<Path>>) -> Result<Self, RifError> {
+        let path = utils::get_snapshots_path(path)?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        let result = bincode::deserialize::<Self>(&std::fs::read(path)?);
+        match result {
+            Err(err) => {
+                Err(RifError::BincodeError(err))
+            }
+            Ok(snapshots) => {
+                Ok(snapshots)
+            }
+        }
+    }
+
+    /// Save snapshots into a file
+    pub fn save_to_file(&self, path: Option<impl AsRef<Path>>) -> Result<(), RifError> {
+        let result = bincode::serialize(self);
+        let path = utils::get_snapshots_path(path)?;
+        if let Err(err) = result {
+            Err(RifError::BincodeError(err))
+        } else {
+            std::fs::write(path, result.unwrap())?;
+            Ok(())
+        }
+    }
+}
+
+/// Make a unified diff of two contents
+///
+/// Removed lines are red and inserted lines are green.
+pub(crate) fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for group in diff.grouped_ops(3) {
+        let (first, last) = (&group[0], &group[group.len() - 1]);
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_range.start + 1, old_range.len(), new_range.start + 1, new_range.len()
+        ));
+        for op in group.iter() {
+            for change in diff.iter_changes(op) {
+                let line = format!("{}{}", change.tag(), change.value());
+                let line = line.trim_end_matches('\n');
+                match change.tag() {
+                    ChangeTag::Delete => output.push_str(&format!("{}\n", utils::red(line))),
+                    ChangeTag::Insert => output.push_str(&format!("{}\n", utils::green(line))),
+                    ChangeTag::Equal => output.push_str(&format!("{}\n", line)),
+                }
+            }
+        }
+    }
+    output
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+use std::io::{Stdout, Write};
+use std::path::{Path, PathBuf};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use itertools::Itertools;
+
+use crate::models::FileStatus;
+use crate::rif::Rif;
+use crate::RifError;
+
+/// Help text for each mode
+const BROWSE_HELP: &str = "j/k: move  l/h: expand/collapse  r: references  a: ack  c: commit  s: stale only  q: quit";
+const PICKER_HELP: &str = "j/k: move  space: set/unset reference  q: back";
+const INPUT_HELP: &str = "enter: commit  esc: cancel";
+
+/// Current interaction mode of terminal ui
+enum Mode {
+    Browse,
+    /// Pick references of a target file
+    Picker { target: PathBuf, cursor: usize },
+    /// Type a commit message
+    Input { buffer: String },
+}
+
+/// Single visible row of relation tree
+///
+/// Chain is a list of paths from top level file to the row's file
+struct Row {
+    chain: Vec<PathBuf>,
+}
+
+impl Row {
+    fn path(&self) -> &Path {
+        self.chain.last().unwrap()
+    }
+}
+
+/// Interactive terminal ui to browse and edit relations
+///
+/// Operations that print logs such as commit and ack are executed
+/// after leaving alternate screen so that logs can be read.
+pub(crate) struct Tui<'a> {
+    rif: &'a mut Rif,
+    stdout: Stdout,
+    mode: Mode,
+    cursor: usize,
+    scroll: usize,
+    stale_only: bool,
+    expanded: HashSet<Vec<PathBuf>>,
+    message: String,
+}
+
+impl<'a> Tui<'a> {
+    pub fn new(rif: &'a mut Rif) -> Self {
+        Self {
+            rif,
+            stdout: std::io::stdout(),
+            mode: Mode::Browse,
+            cursor: 0,
+            scroll: 0,
+            stale_only: false,
+            expanded: HashSet::new(),
+            message: String::new(),
+        }
+    }
+
+    /// Run terminal ui until user quits
+    pub fn run(&mut self) -> Result<(), RifError> {
+        self.enter()?;
+        let result = self.event_loop();
+        // Terminal should be restored even if error occured
+        self.leave()?;
+        result
+    }
+
+    fn enter(&mut self) -> Result<(), RifError> {
+        terminal::enable_raw_mode()?;
+        execute!(self.stdout, EnterAlternateScreen, Hide)?;
+        Ok(())
+    }
+
+    fn leave(&mut self) -> Result<(), RifError> {
+        execute!(self.stdout, Show, LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        Ok(())
+    }
+
+    fn event_loop(&mut self) -> Result<(), RifError> {
+        loop {
+            self.draw()?;
+            if let Event::Key(key) = event::read()? {
+                // Ignore key release events on some platforms
+                if key.kind == KeyEventKind::Release {
+                    continue;
+                }
+                let quit = match self.mode {
+                    Mode::Browse => self.handle_browse(key)?,
+                    Mode::Picker { .. } => { self.handle_picker(key)?; false }
+                    Mode::Input { .. } => { self.handle_input(key)?; false }
+                };
+                if quit {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Get visible rows of relation tree
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        for path in self.rif.relation.files.keys().sorted() {
+            if self.stale_only && self.rif.relation.files.get(path).unwrap().status != FileStatus::Stale {
+                continue;
+            }
+            self.push_rows(vec![path.to_owned()], &mut rows);
+        }
+        rows
+    }
+
+    /// Push a row and its expanded references recursively
+    fn push_rows(&self, chain: Vec<PathBuf>, rows: &mut Vec<Row>) {
+        let references: Vec<PathBuf> = if self.expanded.contains(&chain) {
+            self.rif.relation.files.get(chain.last().unwrap()).unwrap().references.iter().cloned().sorted().collect()
+        } else {
+            vec![]
+        };
+        rows.push(Row { chain: chain.clone() });
+        for reference in references {
+            let mut child = chain.clone();
+            child.push(reference);
+            self.push_rows(child, rows);
+        }
+    }
+
+    /// Get tracked files that can be references of target file
+    fn picker_entries(&self, target: &Path) -> Vec<PathBuf> {
+        self.rif.relation.files.keys().filter(|path| *path != target).cloned().sorted().collect()
+    }
+
+    fn handle_browse(&mut self, key: KeyEvent) -> Result<bool, RifError> {
+        let rows = self.rows();
+        let selected = rows.get(self.cursor).map(|row| row.chain.clone());
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.cursor = (self.cursor + 1).min(rows.len().saturating_sub(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => {
+                if let Some(chain) = selected {
+                    self.expanded.insert(chain);
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                if let Some(chain) = selected {
+                    // Collapse parent row if selected row is not expanded
+                    if !self.expanded.remove(&chain) && chain.len() > 1 {
+                        let parent = chain[..chain.len() - 1].to_vec();
+                        self.expanded.remove(&parent);
+                        self.cursor = self.rows().iter().position(|row| row.chain == parent).unwrap_or(0);
+                    }
+                }
+            }
+            KeyCode::Char('s') => {
+                self.stale_only = !self.stale_only;
+                self.cursor = 0;
+            }
+            KeyCode::Char('r') => {
+                if let Some(chain) = selected {
+                    self.mode = Mode::Picker { target: chain.last().unwrap().to_owned(), cursor: 0 };
+                }
+            }
+            KeyCode::Char('a') => {
+                if let Some(chain) = selected {
+                    let file = chain.last().unwrap().to_owned();
+                    self.suspended(|rif| rif.ack(&[file], None))?;
+                }
+            }
+            KeyCode::Char('c') => {
+                self.mode = Mode::Input { buffer: String::new() };
+            }
+            _ => (),
+        }
+
+        Ok(false)
+    }
+
+    fn handle_picker(&mut self, key: KeyEvent) -> Result<(), RifError> {
+        let (target, cursor) = if let Mode::Picker { target, cursor } = &self.mode {
+            (target.to_owned(), *cursor)
+        } else {
+            return Ok(());
+        };
+        let entries = self.picker_entries(&target);
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.mode = Mode::Browse,
+            KeyCode::Char('j') | KeyCode::Down => {
+                let cursor = (cursor + 1).min(entries.len().saturating_sub(1));
+                self.mode = Mode::Picker { target, cursor };
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.mode = Mode::Picker { target, cursor: cursor.saturating_sub(1) };
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if let Some(reference) = entries.get(cursor) {
+                    let refs = vec![reference.to_owned()];
+                    let result = if self.rif.relation.files.get(&target).unwrap().references.contains(reference) {
+                        self.rif.unset(&target, &refs)
+                    } else {
+                        self.rif.set(&target, &refs)
+                    };
+                    self.message = match result {
+                        Ok(()) => String::new(),
+                        Err(err) => err.to_string(),
+                    };
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn handle_input(&mut self, key: KeyEvent) -> Result<(), RifError> {
+        let mut buffer = if let Mode::Input { buffer } = &self.mode {
+            buffer.to_owned()
+        } else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Browse;
+                return Ok(());
+            }
+            KeyCode::Enter => {
+                self.mode = Mode::Browse;
+                let message = if buffer.is_empty() { None } else { Some(buffer) };
+                self.suspended(|rif| rif.commit(None::<&Vec<PathBuf>>, message.as_deref(), false, false))?;
+                return Ok(());
+            }
+            KeyCode::Backspace => { buffer.pop(); }
+            KeyCode::Char(ch) => buffer.push(ch),
+            _ => (),
+        }
+
+        self.mode = Mode::Input { buffer };
+        Ok(())
+    }
+
+    /// Execute an operation outside of alternate screen
+    ///
+    /// Logs of the operation are shown until user presses a key
+    fn suspended(&mut self, operation: impl FnOnce(&mut Rif) -> Result<(), RifError>) -> Result<(), RifError> {
+        self.leave()?;
+        if let Err(err) = operation(self.rif) {
+            println!("{}", err);
+        }
+        println!("\nPress any key to continue");
+
+        terminal::enable_raw_mode()?;
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release {
+                    break;
+                }
+            }
+        }
+        execute!(self.stdout, EnterAlternateScreen, Hide)?;
+        self.message.clear();
+        Ok(())
+    }
+
+    fn draw(&mut self) -> Result<(), RifError> {
+        let (width, height) = terminal::size()?;
+        let width = width as usize;
+        // Header and footer lines
+        let body_height = (height as usize).saturating_sub(3).max(1);
+
+        queue!(self.stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+
+        let (title, lines, cursor, help) = match &self.mode {
+            Mode::Picker { target, cursor } => {
+                let references = &self.rif.relation.files.get(target).unwrap().references;
+                let lines: Vec<(String, FileStatus)> = self.picker_entries(target)
+                    .into_iter()
+                    .map(|path| {
+                        let mark = if references.contains(&path) { "[x]" } else { "[ ]" };
+                        let status = self.rif.relation.files.get(&path).unwrap().status;
+                        (format!("{} {}", mark, path.display()), status)
+                    })
+                    .collect();
+                (format!("References of {}", target.display()), lines, *cursor, PICKER_HELP)
+            }
+            _ => {
+                let lines: Vec<(String, FileStatus)> = self.rows()
+                    .iter()
+                    .map(|row| {
+                        let indent = "  ".repeat(row.chain.len() - 1);
+                        let prefix = if row.chain.len() == 1 { ">" } else { "- >" };
+                        let status = self.rif.relation.files.get(row.path()).unwrap().status;
+                        (format!("{}{} {}", indent, prefix, row.path().display()), status)
+                    })
+                    .collect();
+                let filter = if self.stale_only { " (stale only)" } else { "" };
+                let help = if let Mode::Input { .. } = self.mode { INPUT_HELP } else { BROWSE_HELP };
+                (format!("Rif relations{}", filter), lines, self.cursor, help)
+            }
+        };
+
+        // Keep cursor inside of visible area
+        if cursor < self.scroll {
+            self.scroll = cursor;
+        } else if cursor >= self.scroll + body_height {
+            self.scroll = cursor + 1 - body_height;
+        }
+
+        queue!(self.stdout, SetAttribute(Attribute::Bold), Print(truncate(&title, width)), SetAttribute(Attribute::Reset))?;
+        for (index, (line, status)) in lines.iter().enumerate().skip(self.scroll).take(body_height) {
+            let row = (index - self.scroll + 1) as u16;
+            let (color, mark) = match status {
+                FileStatus::Fresh => (Color::Green, ""),
+                FileStatus::Stale => (Color::Red, " (s)"),
+                FileStatus::Neutral => (Color::Blue, " (f)"),
+            };
+            queue!(self.stdout, MoveTo(0, row))?;
+            if index == cursor {
+                queue!(self.stdout, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                self.stdout,
+                SetForegroundColor(color),
+                Print(truncate(&format!("{}{}", line, mark), width)),
+                ResetColor,
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+
+        let footer = if let Mode::Input { buffer } = &self.mode {
+            format!("Commit message : {}", buffer)
+        } else {
+            self.message.clone()
+        };
+        queue!(
+            self.stdout,
+            MoveTo(0, height.saturating_sub(2)),
+            Print(truncate(&footer, width)),
+            MoveTo(0, height.saturating_sub(1)),
+            SetAttribute(Attribute::Dim),
+            Print(truncate(help, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Truncate text into given width
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
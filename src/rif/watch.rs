@@ -0,0 +1,59 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use crate::RifError;
+
+/// Watch a directory recursively and call a given function with settled batch of changes
+///
+/// A batch is settled when no filesystem event has arrived for the debounce duration.
+/// Given paths are stripped with the root directory. This function returns only when
+/// watcher fails or given function returns error.
+///
+/// # Args
+///
+/// * `root` - Directory to watch
+/// * `debounce` - Duration to wait for following events
+/// * `f` - Function to be triggered on every settled batch
+pub(crate) fn watch_directory(root: &Path, debounce: Duration, f: &mut dyn FnMut(HashSet<PathBuf>) -> Result<(), RifError>) -> Result<(), RifError> {
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|err| RifError::WatchError(format!("Failed to create watcher : {}", err)))?;
+    watcher.watch(root, RecursiveMode::Recursive)
+        .map_err(|err| RifError::WatchError(format!("Failed to watch \"{}\" : {}", root.display(), err)))?;
+
+    let mut batch: HashSet<PathBuf> = HashSet::new();
+    loop {
+        // Block until the first event of a batch arrives
+        // and then wait until events are settled
+        let received = if batch.is_empty() {
+            receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            receiver.recv_timeout(debounce)
+        };
+
+        match received {
+            Ok(Ok(event)) => {
+                // Reading a file is not a change
+                if let EventKind::Access(_) = event.kind {
+                    continue;
+                }
+                for path in event.paths {
+                    let path = path.strip_prefix(root).map(|p| p.to_owned()).unwrap_or(path);
+                    batch.insert(path);
+                }
+            }
+            Ok(Err(err)) => {
+                return Err(RifError::WatchError(format!("Failed to watch changes : {}", err)));
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                f(std::mem::take(&mut batch))?;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(RifError::WatchError(String::from("Watcher was disconnected")));
+            }
+        }
+    }
+}
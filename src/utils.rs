@@ -4,7 +4,9 @@ use std::fs::File;
 use std::fs::metadata;
 use std::collections::HashSet;
 use std::path::{PathBuf, Path};
-use std::process::Command;
+use std::io::Write;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "color")]
 use colored::*;
@@ -34,6 +36,52 @@ pub fn get_current_unix_time() -> NaiveDateTime {
     unix_time
 }
 
+/// Parse time string given by user
+///
+/// Both "%Y-%m-%d %H:%M:%S" and "%Y-%m-%d" formats are accepted, time is regarded as utc.
+pub fn parse_time(raw: &str) -> Result<NaiveDateTime, RifError> {
+    if let Ok(time) = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S") {
+        return Ok(time);
+    }
+    match chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms(0, 0, 0)),
+        Err(_) => Err(RifError::CliError(format!("Invalid time : {}. Use \"YYYY-MM-DD\" or \"YYYY-MM-DD HH:MM:SS\" format", raw))),
+    }
+}
+
+/// Prepend format header to serialized bytes
+///
+/// Header is composed of magic bytes and a little endian version number
+pub fn add_format_header(version: u32, bytes: Vec<u8>) -> Vec<u8> {
+    let mut result = FORMAT_MAGIC.to_vec();
+    result.extend_from_slice(&version.to_le_bytes());
+    result.extend(bytes);
+    result
+}
+
+/// Split format header from bytes
+///
+/// Bytes without header are written before header was introduced, thus their version is 0.
+/// Returns error if version is newer than given latest version.
+/// # Args
+///
+/// * `bytes` - Bytes read from a file
+/// * `latest` - Latest format version that current rif writes
+pub fn split_format_header(bytes: &[u8], latest: u32) -> Result<(u32, &[u8]), RifError> {
+    let header_size = FORMAT_MAGIC.len() + 4;
+    if bytes.len() < header_size || !bytes.starts_with(FORMAT_MAGIC) {
+        return Ok((0, bytes));
+    }
+
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&bytes[FORMAT_MAGIC.len()..header_size]);
+    let version = u32::from_le_bytes(version);
+    if version > latest {
+        return Err(RifError::InvalidFormat(format!("File format version {} is newer than supported version {}. Update rif", version, latest)));
+    }
+    Ok((version, &bytes[header_size..]))
+}
+
 /// Recursively walk directories and call a given function
 ///
 /// Function is called on all paths including files and directories
@@ -230,6 +278,15 @@ pub fn get_rel_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError
     Ok(path.join(RIF_DIECTORY).join(RIF_REL_FILE))
 }
 
+pub fn get_rel_backup_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
+    let path = if let Some(path) = path {
+        path.as_ref().to_owned()
+    } else {  
+        std::env::current_dir()?
+    };
+    Ok(path.join(RIF_DIECTORY).join(RIF_REL_BACKUP))
+}
+
 pub fn get_config_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
     let path = if let Some(path) = path {
         path.as_ref().to_owned()
@@ -248,6 +305,51 @@ pub fn get_history_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifE
     Ok(path.join(RIF_DIECTORY).join(RIF_HIST_FILE))
 }
 
+pub fn get_history_backup_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
+    let path = if let Some(path) = path {
+        path.as_ref().to_owned()
+    } else {  
+        std::env::current_dir()?
+    };
+    Ok(path.join(RIF_DIECTORY).join(RIF_HIST_BACKUP))
+}
+
+pub fn get_commit_message_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
+    let path = if let Some(path) = path {
+        path.as_ref().to_owned()
+    } else {  
+        std::env::current_dir()?
+    };
+    Ok(path.join(RIF_DIECTORY).join(RIF_COMMIT_MESSAGE))
+}
+
+pub fn get_snapshots_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
+    let path = if let Some(path) = path {
+        path.as_ref().to_owned()
+    } else {  
+        std::env::current_dir()?
+    };
+    Ok(path.join(RIF_DIECTORY).join(RIF_SNAPSHOTS))
+}
+
+pub fn get_objects_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
+    let path = if let Some(path) = path {
+        path.as_ref().to_owned()
+    } else {  
+        std::env::current_dir()?
+    };
+    Ok(path.join(RIF_DIECTORY).join(RIF_OBJECTS))
+}
+
+pub fn get_object_refs_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
+    let path = if let Some(path) = path {
+        path.as_ref().to_owned()
+    } else {  
+        std::env::current_dir()?
+    };
+    Ok(path.join(RIF_DIECTORY).join(RIF_OBJECT_REFS))
+}
+
 pub fn get_meta_path(path : Option<impl AsRef<Path>>) -> Result<PathBuf, RifError> {
     let path = if let Some(path) = path {
         path.as_ref().to_owned()
@@ -289,26 +391,102 @@ pub fn yellow(string : &str) -> Box<dyn std::fmt::Display> {
     Box::new(string.to_owned())
 }
 
-pub fn cmd(cmd: &str, args: Vec<String>) -> Result<(), RifError> {
-    let output = if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .arg("/C")
-            .args(args)
-            .output()
-            .expect("failed to execute process")
+/// Build a command with given arguments
+///
+/// Shell mode executes the command through system shell so that pipes and quotings work.
+/// Arguments are passed as positional parameters in shell mode.
+///
+/// # Args
+///
+/// * `cmd` - Command to build
+/// * `args` - Arguments to pass to the command
+/// * `shell` - Whether to execute the command through system shell
+pub fn build_command(cmd: &str, args: Vec<String>, shell: bool) -> Command {
+    if !shell {
+        let mut command = Command::new(cmd);
+        command.args(args);
+        return command;
+    }
+
+    if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(cmd).args(args);
+        command
+    } else {
+        // "$0" is set to rif and the others are set to given arguments
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!("{} \"$@\"", cmd)).arg("rif").args(args);
+        command
+    }
+}
+
+/// Run a command and wait for its exit
+///
+/// Stdout and stderr of the command are streamed into those of rif.
+/// Command is killed when it doesn't exit until timeout. On unix, command with timeout runs
+/// in its own process group so that processes started by the command are also killed.
+///
+/// # Args
+///
+/// * `command` - Command to run
+/// * `stdin` - Content to write into the command's stdin
+/// * `timeout` - Time limit to wait for the command
+pub fn run_command(mut command: Command, stdin: Option<String>, timeout: Option<Duration>) -> Result<ExitStatus, RifError> {
+    let program = command.get_program().to_string_lossy().to_string();
+    #[cfg(unix)]
+    if timeout.is_some() {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| RifError::HookError(format!("Failed to execute \"{}\" : {}", program, err)))?;
+
+    // Write in a separate thread so that a process which doesn't read stdin can't block rif
+    let writer = if let (Some(content), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        Some(std::thread::spawn(move || child_stdin.write_all(content.as_bytes())))
     } else {
-        Command::new(cmd)
-            .args(args)
-            .output()
-            .expect("failed to execute process")
+        None
     };
 
-    // This can be not valid, since it is lossy, but hardly 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let status = if let Some(timeout) = timeout {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if start.elapsed() >= timeout {
+                kill_process_group(&mut child)?;
+                child.wait()?;
+                return Err(RifError::HookError(format!("\"{}\" didn't exit within {} seconds and was killed", program, timeout.as_secs())));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    } else {
+        child.wait()?
+    };
+
+    if let Some(writer) = writer {
+        // Process may exit without reading stdin, which is not an error
+        if let Ok(Err(err)) = writer.join() {
+            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(RifError::IoError(err));
+            }
+        }
+    }
 
-    if stdout.len() != 0 { println!("{}",stdout); }
-    if stderr.len() != 0 { eprintln!("{}",stderr); }
+    Ok(status)
+}
 
-    Ok(())
+/// Kill a child process and processes in its process group
+fn kill_process_group(child: &mut Child) -> std::io::Result<()> {
+    // Group may be already gone, which is not an error
+    #[cfg(unix)]
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+    child.kill()
 }